@@ -5,7 +5,6 @@ use ic_kit::ic::call;
 use ic_kit::macros::*;
 use ic_kit::*;
 use std::collections::BTreeMap;
-use std::ops::Bound::Included;
 use unic::emoji::char::is_emoji;
 use unic::emoji::*;
 
@@ -72,11 +71,64 @@ impl AddressBook {
         }
     }
 
-    pub fn add(&mut self, account: Principal, address: Address) -> Result<(), Failure> {
+    pub fn add(
+        &mut self,
+        account: Principal,
+        address: Address,
+        overwrite: bool,
+    ) -> Result<(), Failure> {
         let pointer: Key = (account, address.name.clone());
 
-        self.0.insert(pointer.clone(), address);
-        return Ok(());
+        if self.0.contains_key(&pointer) && !overwrite {
+            return Err(already_exists());
+        }
+
+        self.0.insert(pointer, address);
+        Ok(())
+    }
+
+    pub fn get(&self, account: Principal, name: String) -> Option<&Address> {
+        self.0.get(&(account, name))
+    }
+
+    // The name of an entry is changed with `rename`
+    pub fn update(
+        &mut self,
+        account: Principal,
+        name: String,
+        address: Address,
+    ) -> Result<(), Failure> {
+        if address.name != name {
+            return Err(Failure::BadParameters);
+        }
+
+        match self.0.get_mut(&(account, name)) {
+            Some(entry) => {
+                *entry = address;
+                Ok(())
+            }
+            None => Err(Failure::NonExistentItem),
+        }
+    }
+
+    pub fn rename(
+        &mut self,
+        account: Principal,
+        old_name: String,
+        new_name: String,
+    ) -> Result<(), Failure> {
+        if self.0.contains_key(&(account, new_name.clone())) {
+            return Err(already_exists());
+        }
+
+        match self.0.remove(&(account, old_name)) {
+            Some(mut address) => {
+                address.name = new_name.clone();
+                self.0.insert((account, new_name), address);
+                Ok(())
+            }
+            None => Err(Failure::NonExistentItem),
+        }
     }
 
     pub fn remove(&mut self, account: Principal, canister_name: String) -> Result<(), Failure> {
@@ -90,12 +142,15 @@ impl AddressBook {
         return Ok(());
     }
 
+    // The entries of an account, the keys are ordered by account first
+    fn entries(&self, account: Principal) -> impl Iterator<Item = (&Key, &Address)> {
+        self.0
+            .range((account, String::new())..)
+            .take_while(move |((owner, _), _)| *owner == account)
+    }
+
     pub fn get_all(&self, account: Principal) -> Vec<(&Key, &Address)> {
-        let start: Key = (account.clone(), String::new());
-        let end: Key = (account.clone(), unsafe {
-            String::from(std::char::from_u32_unchecked(u32::MAX))
-        });
-        self.0.range((Included(start), Included(end))).collect()
+        self.entries(account).collect()
     }
 
     pub fn get_all_paginated(
@@ -110,12 +165,8 @@ impl AddressBook {
             return Err(Failure::BadParameters);
         }
 
-        let start: Key = (account.clone(), String::new());
-        let end: Key = (account.clone(), unsafe {
-            String::from(std::char::from_u32_unchecked(u32::MAX))
-        });
         let addresses: Vec<(&(ic_kit::Principal, std::string::String), &Address)> =
-            self.0.range((Included(start), Included(end))).collect();
+            self.entries(account).collect();
 
         if offset + limit > addresses.len() {
             limit = addresses.len() - offset;
@@ -125,16 +176,28 @@ impl AddressBook {
     }
 }
 
+fn already_exists() -> Failure {
+    Failure::Unknown(String::from("An address with this name already exists"))
+}
+
 #[query]
 fn name() -> String {
     String::from("Address Book")
 }
 
-#[update]
-pub async fn add(address: Address) -> Result<(), Failure> {
-    if &address.name.len() > &NAME_LIMIT {
+// Names are the keys of the entries, they cannot be blank
+fn validate_name(name: &str) -> Result<(), Failure> {
+    if name.trim().is_empty() || name.len() > NAME_LIMIT {
         return Err(Failure::BadParameters);
-    } else if address.description.is_some() {
+    }
+
+    Ok(())
+}
+
+fn validate_address(address: &Address) -> Result<(), Failure> {
+    validate_name(&address.name)?;
+
+    if address.description.is_some() {
         let description = address.clone().description.unwrap();
 
         if &description.len() > &DESCRIPTION_LIMIT {
@@ -148,14 +211,48 @@ pub async fn add(address: Address) -> Result<(), Failure> {
         }
     }
 
+    Ok(())
+}
+
+// An entry with the same name is only replaced when `overwrite` is set
+#[update]
+pub async fn add(address: Address, overwrite: Option<bool>) -> Result<(), Failure> {
+    validate_address(&address)?;
+
+    let caller = ic::caller();
+
+    let address_book = ic::get_mut::<AddressBook>();
+    address_book
+        .validate_address_type(address.value.clone())
+        .await?;
+    address_book.add(caller, address, overwrite.unwrap_or(false))
+}
+
+#[update]
+pub fn get(address_name: String) -> Option<&'static Address> {
+    let address_book = ic::get_mut::<AddressBook>();
+    address_book.get(ic::caller(), address_name)
+}
+
+#[update]
+pub async fn update(address_name: String, address: Address) -> Result<(), Failure> {
+    validate_address(&address)?;
+
     let caller = ic::caller();
 
     let address_book = ic::get_mut::<AddressBook>();
     address_book
         .validate_address_type(address.value.clone())
         .await?;
-    address_book.add(caller.clone(), address.clone());
-    return Ok(());
+    address_book.update(caller, address_name, address)
+}
+
+#[update]
+pub fn rename(old_name: String, new_name: String) -> Result<(), Failure> {
+    validate_name(&new_name)?;
+
+    let address_book = ic::get_mut::<AddressBook>();
+    address_book.rename(ic::caller(), old_name, new_name)
 }
 
 #[update]
@@ -1,11 +1,20 @@
 #[cfg(test)]
 mod tests {
-    use ic_kit::{mock_principals, MockContext};
+    use ic_kit::{mock_principals, MockContext, Principal};
     use tokio::*;
 
     use crate::address_book::*;
     use crate::common_types::*;
 
+    fn contact(name: &str, principal_id: Principal) -> Address {
+        Address {
+            name: String::from(name),
+            description: Some(String::from("Friend")),
+            emoji: Some(String::from("😚")),
+            value: AddressType::PrincipalId(principal_id),
+        }
+    }
+
     #[tokio::test]
     async fn test_add_principal_id_address_successfully() {
         MockContext::new()
@@ -19,7 +28,7 @@ mod tests {
             value: AddressType::PrincipalId(mock_principals::bob()),
         };
 
-        let addition_result = add(address_info.clone()).await;
+        let addition_result = add(address_info.clone(), None).await;
         assert!(addition_result.is_ok());
 
         let addresses = get_all();
@@ -42,7 +51,7 @@ mod tests {
             )),
         };
 
-        let addition_result = add(address_info.clone()).await;
+        let addition_result = add(address_info.clone(), None).await;
         assert!(addition_result.is_ok());
 
         let addresses = get_all();
@@ -67,7 +76,7 @@ mod tests {
             value: AddressType::PrincipalId(mock_principals::bob()),
         };
 
-        let addition_result = add(address_info.clone()).await;
+        let addition_result = add(address_info.clone(), None).await;
         assert!(addition_result.is_err());
         assert_eq!(addition_result.unwrap_err(), Failure::BadParameters);
     }
@@ -85,7 +94,7 @@ mod tests {
             value: AddressType::PrincipalId(mock_principals::bob()),
         };
 
-        let addition_result = add(address_info.clone()).await;
+        let addition_result = add(address_info.clone(), None).await;
         assert!(addition_result.is_err());
         assert_eq!(addition_result.unwrap_err(), Failure::BadParameters);
     }
@@ -121,7 +130,7 @@ mod tests {
             value: AddressType::PrincipalId(mock_principals::bob()),
         };
 
-        let addition_result = add(address_info.clone()).await;
+        let addition_result = add(address_info.clone(), None).await;
         assert!(addition_result.is_ok());
 
         let removal_result = remove(String::from("Bob"));
@@ -151,7 +160,7 @@ mod tests {
 
         // Alice adds Bob as her contact
         context.update_caller(mock_principals::alice());
-        add(bob_address_info).await;
+        add(bob_address_info, None).await;
 
         let alice_addresses = get_all();
 
@@ -160,7 +169,7 @@ mod tests {
 
         // Bob adds Alice as his contact
         context.update_caller(mock_principals::bob());
-        add(alice_address_info).await;
+        add(alice_address_info, None).await;
 
         let bob_addresses = get_all();
 
@@ -188,8 +197,8 @@ mod tests {
             value: AddressType::PrincipalId(mock_principals::alice()),
         };
 
-        add(bob_address_info).await;
-        add(andrew_address_info).await;
+        add(bob_address_info, None).await;
+        add(andrew_address_info, None).await;
 
         let addresses = get_all();
 
@@ -219,7 +228,7 @@ mod tests {
             value: AddressType::PrincipalId(mock_principals::bob()),
         };
 
-        let addition_result = add(address_info.clone()).await;
+        let addition_result = add(address_info.clone(), None).await;
         assert!(addition_result.is_ok());
 
         let addresses = get_all_paginated(Some(0), Some(1)).unwrap();
@@ -240,11 +249,107 @@ mod tests {
             value: AddressType::PrincipalId(mock_principals::bob()),
         };
 
-        let addition_result = add(address_info.clone()).await;
+        let addition_result = add(address_info.clone(), None).await;
         assert!(addition_result.is_ok());
 
         let addresses = get_all_paginated(Some(2), Some(1));
 
         assert!(addresses.is_err());
     }
+
+    #[tokio::test]
+    async fn test_add_rejects_duplicate_names_unless_overwritten() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        assert!(add(contact("Bob", mock_principals::bob()), None)
+            .await
+            .is_ok());
+        assert!(matches!(
+            add(contact("Bob", mock_principals::john()), Some(false)).await,
+            Err(Failure::Unknown(_))
+        ));
+        assert_eq!(
+            get(String::from("Bob")).unwrap().value,
+            AddressType::PrincipalId(mock_principals::bob())
+        );
+
+        assert!(add(contact("Bob", mock_principals::john()), Some(true))
+            .await
+            .is_ok());
+        assert_eq!(
+            get(String::from("Bob")).unwrap().value,
+            AddressType::PrincipalId(mock_principals::john())
+        );
+        assert_eq!(get_all().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_update_and_rename_addresses() {
+        let context = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        assert!(get(String::from("Bob")).is_none());
+        assert_eq!(
+            update(String::from("Bob"), contact("Bob", mock_principals::bob())).await,
+            Err(Failure::NonExistentItem)
+        );
+
+        assert!(add(contact("Bob", mock_principals::bob()), None)
+            .await
+            .is_ok());
+        assert!(add(contact("John", mock_principals::john()), None)
+            .await
+            .is_ok());
+
+        let mut edited = contact("Bob", mock_principals::bob());
+        edited.description = Some(String::from("Colleague"));
+        assert!(update(String::from("Bob"), edited).await.is_ok());
+        assert_eq!(
+            get(String::from("Bob")).unwrap().description,
+            Some(String::from("Colleague"))
+        );
+        assert_eq!(
+            update(
+                String::from("Bob"),
+                contact("Robert", mock_principals::bob())
+            )
+            .await,
+            Err(Failure::BadParameters)
+        );
+
+        // The new name has to be free
+        assert!(matches!(
+            rename(String::from("Bob"), String::from("John")),
+            Err(Failure::Unknown(_))
+        ));
+        assert_eq!(
+            rename(String::from("Alice"), String::from("Robert")),
+            Err(Failure::NonExistentItem)
+        );
+        // It is validated like the names of `add`
+        assert_eq!(
+            rename(String::from("Bob"), String::from(" ")),
+            Err(Failure::BadParameters)
+        );
+        assert_eq!(
+            add(contact("", mock_principals::bob()), None).await,
+            Err(Failure::BadParameters)
+        );
+        assert!(rename(String::from("Bob"), String::from("Robert")).is_ok());
+        assert!(get(String::from("Bob")).is_none());
+        let robert = get(String::from("Robert")).unwrap();
+        assert_eq!(robert.name, String::from("Robert"));
+        assert_eq!(robert.description, Some(String::from("Colleague")));
+
+        // The entries of other users are not visible
+        context.update_caller(mock_principals::bob());
+        assert!(get(String::from("Robert")).is_none());
+        assert_eq!(
+            rename(String::from("Robert"), String::from("Bob")),
+            Err(Failure::NonExistentItem)
+        );
+    }
 }
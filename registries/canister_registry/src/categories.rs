@@ -0,0 +1,171 @@
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::collections::BTreeMap;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+use crate::registry::CanisterDB;
+
+// Categories that the NFT and token registries assign to their entries
+pub const BUILTIN_CATEGORIES: [(&str, &str); 2] = [
+    ("NFT", "NFT collections listed through the NFT registry"),
+    ("Token", "Tokens listed through the token registry"),
+];
+
+pub struct Categories(BTreeMap<String, Category>);
+
+impl Default for Categories {
+    fn default() -> Self {
+        let categories = BUILTIN_CATEGORIES
+            .iter()
+            .map(|(name, description)| {
+                (
+                    name.to_string(),
+                    Category {
+                        name: name.to_string(),
+                        description: description.to_string(),
+                        parent: None,
+                    },
+                )
+            })
+            .collect();
+
+        Self(categories)
+    }
+}
+
+impl Categories {
+    pub fn archive(&mut self) -> Vec<Category> {
+        let map = std::mem::take(&mut self.0);
+        map.into_values().collect()
+    }
+
+    pub fn load(&mut self, archive: Vec<Category>) {
+        self.0 = archive
+            .into_iter()
+            .map(|category| (category.name.clone(), category))
+            .collect();
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    pub fn add(&mut self, category: Category) -> Result<(), OperationError> {
+        let config = ic::get::<Config>();
+        if category.name.is_empty()
+            || category.name.len() > config.name_limit
+            || category.description.len() > config.description_limit
+        {
+            return Err(OperationError::BadParameters);
+        }
+
+        // The parent has to exist, and must not be the category itself or one of its descendants
+        if let Some(parent) = &category.parent {
+            if !self.contains(parent) || self.descendants(&category.name).contains(parent) {
+                return Err(OperationError::BadParameters);
+            }
+        }
+
+        self.0.insert(category.name.clone(), category);
+        Ok(())
+    }
+
+    // Registers a free text category of an entry that was listed before the categories were managed.
+    // It is kept as it is, the limits of `add` did not apply to it.
+    pub fn insert_legacy(&mut self, name: &str) {
+        self.0.entry(name.to_string()).or_insert_with(|| Category {
+            name: name.to_string(),
+            description: String::new(),
+            parent: None,
+        });
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), OperationError> {
+        if !self.contains(name) {
+            return Err(OperationError::NonExistentItem);
+        }
+
+        let has_children = self
+            .0
+            .values()
+            .any(|category| category.parent.as_deref() == Some(name));
+        let is_builtin = BUILTIN_CATEGORIES
+            .iter()
+            .any(|(builtin, _)| *builtin == name);
+
+        if has_children || is_builtin {
+            return Err(OperationError::BadParameters);
+        }
+
+        self.0.remove(name);
+        Ok(())
+    }
+
+    pub fn get_all(&self) -> Vec<&Category> {
+        self.0.values().collect()
+    }
+
+    // Returns the category itself followed by all of its subcategories
+    pub fn descendants(&self, name: &str) -> Vec<String> {
+        let mut result = vec![name.to_string()];
+        let mut index = 0;
+
+        while index < result.len() {
+            let children = self
+                .0
+                .values()
+                .filter(|category| category.parent.as_ref() == Some(&result[index]))
+                .map(|category| category.name.clone())
+                .collect::<Vec<String>>();
+
+            result.extend(children);
+            index += 1;
+        }
+
+        result
+    }
+}
+
+#[update]
+pub fn add_category(category: Category) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    ic::get_mut::<Categories>().add(category)
+}
+
+#[update]
+pub fn remove_category(name: String) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    // Categories that are still assigned to an entry can not be removed
+    if !ic::get::<CanisterDB>()
+        .get_by_category(std::slice::from_ref(&name))
+        .is_empty()
+    {
+        return Err(OperationError::BadParameters);
+    }
+
+    ic::get_mut::<Categories>().remove(&name)
+}
+
+#[query]
+pub fn get_categories() -> Vec<&'static Category> {
+    ic::get::<Categories>().get_all()
+}
+
+#[query]
+pub fn get_by_category(name: String) -> Result<Vec<CanisterMetadata>, OperationError> {
+    let categories = ic::get::<Categories>();
+
+    if !categories.contains(&name) {
+        return Err(OperationError::NonExistentItem);
+    }
+
+    let canister_db = ic::get::<CanisterDB>();
+    Ok(canister_db.get_by_category(&categories.descendants(&name)))
+}
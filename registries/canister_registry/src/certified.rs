@@ -0,0 +1,48 @@
+use ic_certified_map::{labeled, labeled_hash, leaf_hash, AsHashTree, Hash, HashTree, RbTree};
+use ic_kit::candid::{encode_one, Principal};
+use serde::Serialize;
+use serde_cbor::Serializer;
+
+use crate::common_types::CanisterMetadata;
+
+// The label of the entries in the certified tree, the certified data is the root hash of `canisters`
+pub const CANISTERS_LABEL: &[u8] = b"canisters";
+
+// The leaf of an entry is the hash of its candid encoding
+pub fn entry_hash(canister: &CanisterMetadata) -> Hash {
+    leaf_hash(&encode_one(canister).unwrap())
+}
+
+// (principal id -> entry hash), rebuilt from the stable entries since it only lives on the heap
+#[derive(Default)]
+pub struct CertifiedEntries(RbTree<Vec<u8>, Hash>);
+
+impl CertifiedEntries {
+    pub fn insert(&mut self, canister: &CanisterMetadata) {
+        self.0.insert(
+            canister.principal_id.as_slice().to_vec(),
+            entry_hash(canister),
+        );
+    }
+
+    pub fn remove(&mut self, principal_id: &Principal) {
+        self.0.delete(principal_id.as_slice());
+    }
+
+    pub fn root_hash(&self) -> Hash {
+        labeled_hash(CANISTERS_LABEL, &self.0.root_hash())
+    }
+
+    // Proves the presence or the absence of the entry, the tree reconstructs to `root_hash`
+    pub fn witness(&self, principal_id: &Principal) -> HashTree<'_> {
+        labeled(CANISTERS_LABEL, self.0.witness(principal_id.as_slice()))
+    }
+}
+
+// Encodes a tree the way the agents expect it, as self-describing CBOR
+pub fn encode_tree(tree: &HashTree) -> Vec<u8> {
+    let mut serializer = Serializer::new(vec![]);
+    serializer.self_describe().unwrap();
+    tree.serialize(&mut serializer).unwrap();
+    serializer.into_inner()
+}
@@ -1,7 +1,7 @@
 use ic_kit::{candid::CandidType, Principal};
 use serde::{Deserialize, Serialize};
 
-#[derive(CandidType, Debug, PartialEq)]
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
 pub enum OperationError {
     NotAuthorized,
     BadParameters,
@@ -32,6 +32,87 @@ pub struct CanisterMetadata {
     pub details: Vec<(String, DetailValue)>,
 }
 
+impl From<CanisterMetadata> for AddCanisterInput {
+    fn from(canister: CanisterMetadata) -> Self {
+        Self {
+            name: canister.name,
+            description: canister.description,
+            thumbnail: canister.thumbnail,
+            frontend: canister.frontend,
+            principal_id: canister.principal_id,
+            details: canister.details,
+        }
+    }
+}
+
+impl CanisterMetadata {
+    pub fn category(&self) -> Option<&String> {
+        self.details.iter().find_map(|(key, value)| match value {
+            DetailValue::Text(category) if key == "category" => Some(category),
+            _ => None,
+        })
+    }
+}
+
+// The entry with the certificate of the canister's certified data and the CBOR encoded witness
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct CertifiedResponse {
+    pub metadata: Option<CanisterMetadata>,
+    pub certificate: Option<Vec<u8>>,
+    pub witness: Vec<u8>,
+}
+
+pub type HeaderField = (String, String);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<HeaderField>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<HeaderField>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+// The outcome of one item of a batch call, in the same order as the input
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct BatchResult {
+    pub principal_id: Principal,
+    pub result: Result<Option<String>, OperationError>,
+}
+
+// Each flag points at the existing entry that the submission conflicts with
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub enum Flag {
+    SimilarName(Principal),
+    DuplicateThumbnail(Principal),
+    DuplicateFrontend(Principal),
+    DuplicateDescription(Principal),
+}
+
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct PendingEntry {
+    pub submitter: Principal,
+    pub submitted_at: u64,
+    pub metadata: AddCanisterInput,
+    pub flags: Vec<Flag>,
+}
+
+#[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
+pub struct CanisterRevision {
+    pub revision: u64,
+    pub author: Principal,
+    pub timestamp: u64,
+    pub metadata: CanisterMetadata,
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub enum DetailValue {
     True,
@@ -46,5 +127,71 @@ pub enum DetailValue {
     Vec(Vec<DetailValue>),
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Category {
+    pub name: String,
+    pub description: String,
+    pub parent: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ListFilter {
+    pub category: Option<String>,
+    pub submitter: Option<Principal>,
+    pub updated_since: Option<u64>,
+}
+
+impl ListFilter {
+    pub fn matches(&self, canister: &CanisterMetadata) -> bool {
+        if self.category.is_some() && canister.category() != self.category.as_ref() {
+            return false;
+        }
+
+        if let Some(submitter) = self.submitter {
+            if canister.submitter != submitter {
+                return false;
+            }
+        }
+
+        if let Some(updated_since) = self.updated_since {
+            if canister.last_updated_at < updated_since {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+// Either `offset` or `cursor` can be used to page through the results, but not both
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ListInput {
+    pub offset: Option<usize>,
+    pub cursor: Option<Principal>,
+    pub limit: Option<usize>,
+    pub filter: Option<ListFilter>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ListResponse {
+    pub items: Vec<CanisterMetadata>,
+    pub total: usize,
+    pub next_cursor: Option<Principal>,
+}
+
+// The text limits and extra detail keys of the entries, there is no registry downstream of this one
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Config {
+    pub name_limit: usize,
+    pub description_limit: usize,
+    pub url_limit: usize,
+    // Detail keys that entries can carry besides their category
+    pub allowed_detail_keys: Vec<String>,
+}
+
 pub const DESCRIPTION_LIMIT: usize = 1200;
 pub const NAME_LIMIT: usize = 24;
+pub const URL_LIMIT: usize = 512;
+pub const DEFAULT_LIMIT: usize = 20;
+pub const MAX_LIMIT: usize = 100;
+pub const MAX_BATCH_SIZE: usize = 100;
@@ -0,0 +1,70 @@
+use ic_kit::macros::*;
+use ic_kit::*;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+
+pub const CATEGORY_KEY: &str = "category";
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            name_limit: NAME_LIMIT,
+            description_limit: DESCRIPTION_LIMIT,
+            url_limit: URL_LIMIT,
+            allowed_detail_keys: vec![],
+        }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), OperationError> {
+        let mut keys = self.allowed_detail_keys.clone();
+        keys.sort();
+        keys.dedup();
+
+        if self.name_limit == 0
+            || self.description_limit == 0
+            || self.url_limit == 0
+            || keys.len() != self.allowed_detail_keys.len()
+            || keys.iter().any(|key| key.is_empty() || key == CATEGORY_KEY)
+        {
+            return Err(OperationError::BadParameters);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_allowed_detail(&self, key: &str) -> bool {
+        self.allowed_detail_keys
+            .iter()
+            .any(|allowed| allowed == key)
+    }
+}
+
+// Replaces the default limits with the ones passed to `init`
+pub fn configure(config: Option<Config>) {
+    if let Some(config) = config {
+        if let Err(err) = config.validate() {
+            ic::trap(&format!("Invalid configuration: {:?}", err));
+        }
+
+        ic::store(config);
+    }
+}
+
+#[query]
+pub fn get_config() -> &'static Config {
+    ic::get::<Config>()
+}
+
+#[update]
+pub fn set_config(config: Config) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    config.validate()?;
+    ic::store(config);
+    Ok(())
+}
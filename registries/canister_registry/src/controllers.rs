@@ -0,0 +1,58 @@
+use ic_kit::candid::{CandidType, Deserialize, Principal};
+use ic_kit::*;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::common_types::OperationError;
+
+pub type ControllersResponse =
+    Pin<Box<dyn Future<Output = Result<Vec<Principal>, OperationError>>>>;
+
+#[derive(CandidType, Deserialize)]
+struct CanisterInfoRequest {
+    canister_id: Principal,
+    num_requested_changes: Option<u64>,
+}
+
+// Only the fields the registry needs, the rest of the response is skipped while decoding
+#[derive(CandidType, Deserialize)]
+struct CanisterInfoResponse {
+    controllers: Vec<Principal>,
+}
+
+// Looks up the controllers of a canister, implemented by a mock in the tests
+pub trait ControllerLookup {
+    fn controllers(&self, canister: Principal) -> ControllersResponse;
+}
+
+pub struct ManagementCanister;
+
+impl ControllerLookup for ManagementCanister {
+    fn controllers(&self, canister: Principal) -> ControllersResponse {
+        Box::pin(async move {
+            let request = CanisterInfoRequest {
+                canister_id: canister,
+                num_requested_changes: None,
+            };
+
+            let response: (CanisterInfoResponse,) = ic::call(
+                Principal::management_canister(),
+                "canister_info",
+                (request,),
+            )
+            .await
+            .map_err(|(_code, msg)| OperationError::Unknown(msg))?;
+
+            Ok(response.0.controllers)
+        })
+    }
+}
+
+pub async fn is_controller<L: ControllerLookup>(
+    lookup: &L,
+    caller: &Principal,
+    canister: Principal,
+) -> Result<bool, OperationError> {
+    let controllers = lookup.controllers(canister).await?;
+    Ok(controllers.contains(caller))
+}
@@ -0,0 +1,116 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use serde::Serialize;
+
+use crate::common_types::*;
+use crate::registry::CanisterDB;
+
+// The columns of `list.csv`, the JSON routes use them as keys
+pub const CSV_HEADER: &str = "name,principal_id,description,url,logo_url";
+
+#[derive(Serialize)]
+struct ListRow<'a> {
+    name: &'a str,
+    principal_id: String,
+    description: &'a str,
+    url: Option<&'a str>,
+    logo_url: &'a str,
+}
+
+impl<'a> From<&'a CanisterMetadata> for ListRow<'a> {
+    fn from(canister: &'a CanisterMetadata) -> Self {
+        Self {
+            name: &canister.name,
+            principal_id: canister.principal_id.to_text(),
+            description: &canister.description,
+            url: canister.frontend.as_deref(),
+            logo_url: &canister.thumbnail,
+        }
+    }
+}
+
+// Quotes a field when it contains a separator, a quote or a line break, like the existing list
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn render_csv(canisters: &[CanisterMetadata]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+
+    for canister in canisters {
+        let row = ListRow::from(canister);
+        let fields = [
+            csv_field(row.name),
+            row.principal_id,
+            csv_field(row.description),
+            csv_field(row.url.unwrap_or_default()),
+            csv_field(row.logo_url),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn response(status_code: u16, content_type: &str, body: Vec<u8>) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![
+            (String::from("Content-Type"), String::from(content_type)),
+            (
+                String::from("Access-Control-Allow-Origin"),
+                String::from("*"),
+            ),
+        ],
+        body,
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> HttpResponse {
+    response(200, "application/json", serde_json::to_vec(value).unwrap())
+}
+
+fn error(status_code: u16, message: &str) -> HttpResponse {
+    response(status_code, "text/plain", message.as_bytes().to_vec())
+}
+
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    if request.method != "GET" {
+        return error(405, "Method not allowed");
+    }
+
+    let path = request.url.split('?').next().unwrap_or_default();
+    let canister_db = ic::get::<CanisterDB>();
+
+    match path {
+        "/canisters.json" => {
+            let canisters = canister_db.get_all();
+            let rows: Vec<ListRow> = canisters.iter().map(ListRow::from).collect();
+            json(&rows)
+        }
+        "/canisters.csv" => response(
+            200,
+            "text/csv",
+            render_csv(&canister_db.get_all()).into_bytes(),
+        ),
+        _ => {
+            let canister = path
+                .strip_prefix("/canisters/")
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|text| Principal::from_text(text).ok())
+                .and_then(|principal_id| canister_db.get_info(principal_id));
+
+            match canister {
+                Some(canister) => json(&ListRow::from(&canister)),
+                None => error(404, "Not found"),
+            }
+        }
+    }
+}
@@ -1,5 +1,13 @@
+mod categories;
+mod certified;
 mod common_types;
+mod config;
+mod controllers;
+mod http;
 mod management;
+mod memory;
 mod registry;
+mod review;
+mod search;
 mod tests;
 mod upgrade;
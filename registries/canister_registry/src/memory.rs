@@ -0,0 +1,159 @@
+use ic_kit::candid::{decode_one, encode_one, CandidType, Deserialize, Principal};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::writer::Writer;
+use ic_stable_structures::{BoundedStorable, DefaultMemoryImpl, Memory as _, Storable};
+use std::borrow::Cow;
+
+use crate::common_types::{CanisterMetadata, CanisterRevision};
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+// The heap state that is still written on upgrades: admins, categories and the review queue
+pub const UPGRADES: MemoryId = MemoryId::new(0);
+pub const ENTRIES: MemoryId = MemoryId::new(1);
+pub const REVISIONS: MemoryId = MemoryId::new(2);
+pub const SEARCH_INDEX: MemoryId = MemoryId::new(3);
+
+// The first bytes of the stable memory once it is owned by the memory manager
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+pub const MAX_METADATA_SIZE: u32 = 4096;
+pub const MAX_REVISION_SIZE: u32 = MAX_METADATA_SIZE + 128;
+// Leaves room for the submitter and the update fields that are added when an input is stored
+pub const MAX_INPUT_SIZE: usize = MAX_METADATA_SIZE as usize - 128;
+pub const MAX_TOKEN_SIZE: usize = 32;
+
+// Kept out of the `ic` stores, the stores that are built from it are created while the storage is borrowed
+thread_local! {
+    static MEMORY_MANAGER: MemoryManager<DefaultMemoryImpl> =
+        MemoryManager::init(DefaultMemoryImpl::default());
+}
+
+pub fn get_memory(id: MemoryId) -> Memory {
+    MEMORY_MANAGER.with(|manager| manager.get(id))
+}
+
+// Stable memory that was written by `stable_store` before the registry moved to stable structures
+pub fn is_legacy_layout() -> bool {
+    let memory = DefaultMemoryImpl::default();
+    if memory.size() == 0 {
+        return false;
+    }
+
+    let mut magic = [0; 3];
+    memory.read(0, &mut magic);
+    &magic != MEMORY_MANAGER_MAGIC
+}
+
+// The state is stored as its length followed by its candid encoding
+pub fn save_upgrade_state<T: CandidType>(state: &T) -> Result<(), String> {
+    let bytes = encode_one(state).map_err(|err| err.to_string())?;
+    let mut memory = get_memory(UPGRADES);
+    let mut writer = Writer::new(&mut memory, 0);
+
+    writer
+        .write(&(bytes.len() as u64).to_le_bytes())
+        .and_then(|_| writer.write(&bytes))
+        .map_err(|_| String::from("Could not grow the stable memory"))
+}
+
+// A state that cannot be decoded is an error, the caller has to trap so that the upgrade is rolled back
+pub fn load_upgrade_state<T: CandidType + for<'de> Deserialize<'de>>() -> Result<Option<T>, String>
+{
+    let memory = get_memory(UPGRADES);
+    if memory.size() == 0 {
+        return Ok(None);
+    }
+
+    let mut length = [0; 8];
+    memory.read(0, &mut length);
+    let mut bytes = vec![0; u64::from_le_bytes(length) as usize];
+    memory.read(8, &mut bytes);
+
+    decode_one(&bytes).map(Some).map_err(|err| err.to_string())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StablePrincipal(pub Principal);
+
+// Tuple keys need a default value, the management canister is also the smallest principal id
+impl Default for StablePrincipal {
+    fn default() -> Self {
+        Self(Principal::management_canister())
+    }
+}
+
+impl Storable for StablePrincipal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.0.as_slice().to_vec())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Principal::from_slice(&bytes))
+    }
+}
+
+impl BoundedStorable for StablePrincipal {
+    const MAX_SIZE: u32 = 29;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// A search token, cut to `MAX_TOKEN_SIZE` bytes so that it fits in a key
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Token(pub String);
+
+impl Token {
+    pub fn new(token: &str) -> Self {
+        let mut end = token.len().min(MAX_TOKEN_SIZE);
+        while !token.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        Self(token[..end].to_string())
+    }
+}
+
+impl Storable for Token {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(String::from_utf8(bytes.into_owned()).unwrap())
+    }
+}
+
+impl BoundedStorable for Token {
+    const MAX_SIZE: u32 = MAX_TOKEN_SIZE as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for CanisterMetadata {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_one(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_one(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for CanisterMetadata {
+    const MAX_SIZE: u32 = MAX_METADATA_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for CanisterRevision {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_one(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_one(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for CanisterRevision {
+    const MAX_SIZE: u32 = MAX_REVISION_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
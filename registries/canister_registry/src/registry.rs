@@ -1,27 +1,161 @@
-use ic_cdk::export::candid::Principal;
+use ic_cdk::export::candid::{encode_one, Principal};
 use ic_kit::macros::*;
 use ic_kit::*;
-use std::collections::HashMap;
+use ic_stable_structures::StableBTreeMap;
+use std::ops::Bound::Included;
 use validator::validate_url;
 
+use crate::categories::Categories;
+use crate::certified::{encode_tree, CertifiedEntries};
 use crate::common_types::*;
+use crate::config::{configure, CATEGORY_KEY};
+use crate::controllers::{is_controller, ControllerLookup, ManagementCanister};
 use crate::management::{is_admin, Admins};
+use crate::memory::{
+    get_memory, Memory, StablePrincipal, ENTRIES, MAX_INPUT_SIZE, MAX_METADATA_SIZE,
+    MAX_REVISION_SIZE, REVISIONS,
+};
+use crate::review::{detect_flags, ReviewQueue};
+use crate::search::SearchIndex;
 
-#[derive(Default)]
-pub struct CanisterDB(HashMap<Principal, CanisterMetadata>);
+// (registry map, search index, revision history, certified tree), all but the certified tree live in
+// stable memory
+pub struct CanisterDB(
+    StableBTreeMap<StablePrincipal, CanisterMetadata, Memory>,
+    SearchIndex,
+    StableBTreeMap<(StablePrincipal, u64), CanisterRevision, Memory>,
+    CertifiedEntries,
+);
+
+impl Default for CanisterDB {
+    fn default() -> Self {
+        let entries: StableBTreeMap<StablePrincipal, CanisterMetadata, Memory> =
+            StableBTreeMap::init(get_memory(ENTRIES));
+        let mut certified = CertifiedEntries::default();
+        for (_, canister) in entries.iter() {
+            certified.insert(&canister);
+        }
+
+        Self(
+            entries,
+            SearchIndex::default(),
+            StableBTreeMap::init(get_memory(REVISIONS)),
+            certified,
+        )
+    }
+}
 
 impl CanisterDB {
-    pub fn archive(&mut self) -> Vec<(Principal, CanisterMetadata)> {
-        let map = std::mem::replace(&mut self.0, HashMap::new());
-        map.into_iter().collect()
+    // Moves the entries and revisions of the layout that was serialised on every upgrade. The old layout
+    // did not limit every field, the entries that do not fit in the stable map are reported before
+    // anything is moved.
+    pub fn migrate(
+        &mut self,
+        entries: Vec<(Principal, CanisterMetadata)>,
+        revisions: Vec<(Principal, Vec<CanisterRevision>)>,
+    ) -> Result<(), String> {
+        let too_big = |bytes: Result<Vec<u8>, _>, max_size: u32| {
+            bytes.map_or(true, |bytes| bytes.len() > max_size as usize)
+        };
+        let mut oversized: Vec<String> = entries
+            .iter()
+            .filter(|(_, canister)| too_big(encode_one(canister), MAX_METADATA_SIZE))
+            .map(|(principal_id, _)| principal_id.to_text())
+            .collect();
+        oversized.extend(
+            revisions
+                .iter()
+                .filter(|(_, history)| {
+                    history
+                        .iter()
+                        .any(|revision| too_big(encode_one(revision), MAX_REVISION_SIZE))
+                })
+                .map(|(principal_id, _)| format!("{} (revisions)", principal_id)),
+        );
+        if !oversized.is_empty() {
+            return Err(format!(
+                "These entries are too big to be migrated: {}",
+                oversized.join(", ")
+            ));
+        }
+
+        for (principal_id, canister) in entries {
+            self.1.insert(&canister);
+            self.3.insert(&canister);
+            self.0.insert(StablePrincipal(principal_id), canister);
+        }
+        self.certify();
+
+        for (principal_id, history) in revisions {
+            for revision in history {
+                self.2
+                    .insert((StablePrincipal(principal_id), revision.revision), revision);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn revisions_of(
+        &self,
+        principal_id: &Principal,
+    ) -> impl Iterator<Item = ((StablePrincipal, u64), CanisterRevision)> + '_ {
+        let key = StablePrincipal(*principal_id);
+        self.2
+            .range((Included((key, 0)), Included((key, u64::MAX))))
+    }
+
+    // Has to be called after every change of the entries, the certified data is the root of the tree
+    pub fn certify(&self) {
+        ic::set_certified_data(&self.3.root_hash());
+    }
+
+    // Keeps the version that is being replaced or removed in the history of the entry
+    fn push_revision(&mut self, canister: CanisterMetadata) {
+        let revision = self.revisions_of(&canister.principal_id).count() as u64;
+        let key = (StablePrincipal(canister.principal_id), revision);
+
+        self.2.insert(
+            key,
+            CanisterRevision {
+                revision,
+                author: canister.last_updated_by,
+                timestamp: canister.last_updated_at,
+                metadata: canister,
+            },
+        );
+    }
+
+    // Stores the entry and keeps the search index, the revision history and the certified tree in sync
+    fn store(&mut self, canister: CanisterMetadata) {
+        let key = StablePrincipal(canister.principal_id);
+
+        if let Some(previous) = self.0.insert(key, canister.clone()) {
+            self.1.remove(&previous);
+            self.push_revision(previous);
+        }
+
+        self.1.insert(&canister);
+        self.3.insert(&canister);
+        self.certify();
     }
 
-    pub fn load(&mut self, archive: Vec<(Principal, CanisterMetadata)>) {
-        self.0 = archive.into_iter().collect();
+    pub fn get_info(&self, canister: Principal) -> Option<CanisterMetadata> {
+        self.0.get(&StablePrincipal(canister))
     }
 
-    pub fn get_info(&mut self, canister: Principal) -> Option<&CanisterMetadata> {
-        self.0.get(&canister)
+    // If its an update, check if the caller matches the submitter or if its an admin
+    pub fn check_authorization(
+        &self,
+        caller: &Principal,
+        principal_id: &Principal,
+    ) -> Result<(), OperationError> {
+        match self.get_info(*principal_id) {
+            Some(canister) if !is_admin(caller) && canister.submitter != *caller => {
+                Err(OperationError::NotAuthorized)
+            }
+            _ => Ok(()),
+        }
     }
 
     pub fn add_canister(
@@ -29,43 +163,41 @@ impl CanisterDB {
         caller: &Principal,
         metadata: AddCanisterInput,
     ) -> Result<(), OperationError> {
-        let canister = self.0.get(&metadata.principal_id);
+        self.check_authorization(caller, &metadata.principal_id)?;
 
-        // If its an update, check if the caller matches the submitter or if its an admin
-        if canister.is_some() && !is_admin(caller) && canister.unwrap().submitter != *caller {
-            return Err(OperationError::NotAuthorized);
-        }
-        // An admin can update any entry
-        else if canister.is_some() && is_admin(caller) {
-            let updated_canister = CanisterMetadata {
-                name: metadata.name,
-                description: metadata.description,
-                thumbnail: metadata.thumbnail,
-                frontend: metadata.frontend,
-                principal_id: metadata.principal_id,
-                submitter: canister.unwrap().submitter,
-                last_updated_by: *caller,
-                last_updated_at: ic::time(),
-                details: metadata.details.clone(),
-            };
-
-            self.0.insert(metadata.principal_id, updated_canister);
-        }
-        // Its a new entry
-        else {
-            let new_canister = CanisterMetadata {
-                name: metadata.name,
-                description: metadata.description,
-                thumbnail: metadata.thumbnail,
-                frontend: metadata.frontend,
-                principal_id: metadata.principal_id,
-                submitter: *caller,
-                last_updated_by: *caller,
-                last_updated_at: ic::time(),
-                details: metadata.details.clone(),
-            };
-
-            self.0.insert(metadata.principal_id, new_canister);
+        match self.get_info(metadata.principal_id) {
+            // An admin can update any entry
+            Some(canister) if is_admin(caller) => {
+                let updated_canister = CanisterMetadata {
+                    name: metadata.name,
+                    description: metadata.description,
+                    thumbnail: metadata.thumbnail,
+                    frontend: metadata.frontend,
+                    principal_id: metadata.principal_id,
+                    submitter: canister.submitter,
+                    last_updated_by: *caller,
+                    last_updated_at: ic::time(),
+                    details: metadata.details.clone(),
+                };
+
+                self.store(updated_canister);
+            }
+            // Its a new entry
+            _ => {
+                let new_canister = CanisterMetadata {
+                    name: metadata.name,
+                    description: metadata.description,
+                    thumbnail: metadata.thumbnail,
+                    frontend: metadata.frontend,
+                    principal_id: metadata.principal_id,
+                    submitter: *caller,
+                    last_updated_by: *caller,
+                    last_updated_at: ic::time(),
+                    details: metadata.details.clone(),
+                };
+
+                self.store(new_canister);
+            }
         }
 
         return Ok(());
@@ -76,29 +208,115 @@ impl CanisterDB {
         caller: &Principal,
         principal_id: &Principal,
     ) -> Result<(), OperationError> {
-        if !self.0.contains_key(principal_id) {
-            return Err(OperationError::NonExistentItem);
-        }
-
-        let canister = self.0.get(principal_id).unwrap();
+        let canister = self
+            .get_info(*principal_id)
+            .ok_or(OperationError::NonExistentItem)?;
 
         if canister.submitter != *caller && !is_admin(caller) {
             return Err(OperationError::NotAuthorized);
         }
 
-        self.0.remove(principal_id);
+        self.0.remove(&StablePrincipal(*principal_id));
+        self.1.remove(&canister);
+        self.3.remove(principal_id);
+        self.certify();
+        self.push_revision(canister);
 
         return Ok(());
     }
 
-    pub fn get_all(&self) -> Vec<&CanisterMetadata> {
-        self.0.values().collect()
+    pub fn get_revisions(&self, principal_id: &Principal) -> Vec<CanisterRevision> {
+        self.revisions_of(principal_id)
+            .map(|(_, revision)| revision)
+            .collect()
+    }
+
+    // Restores a previous version of an entry, the replaced version becomes a revision itself
+    pub fn revert(
+        &mut self,
+        caller: &Principal,
+        principal_id: &Principal,
+        revision: u64,
+    ) -> Result<(), OperationError> {
+        let mut canister = self
+            .2
+            .get(&(StablePrincipal(*principal_id), revision))
+            .ok_or(OperationError::NonExistentItem)?
+            .metadata;
+
+        // The category of the revision may have been removed, or the config changed, since it was stored
+        validate_input(&AddCanisterInput::from(canister.clone()))?;
+
+        canister.last_updated_by = *caller;
+        canister.last_updated_at = ic::time();
+        self.store(canister);
+
+        Ok(())
+    }
+
+    pub fn get_all(&self) -> Vec<CanisterMetadata> {
+        self.0.iter().map(|(_, canister)| canister).collect()
+    }
+
+    pub fn get_by_category(&self, categories: &[String]) -> Vec<CanisterMetadata> {
+        self.0
+            .iter()
+            .map(|(_, canister)| canister)
+            .filter(|canister| matches!(canister.category(), Some(category) if categories.contains(category)))
+            .collect()
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<CanisterMetadata> {
+        self.1
+            .search(query)
+            .iter()
+            .take(limit)
+            .filter_map(|(principal_id, _)| self.get_info(*principal_id))
+            .collect()
+    }
+
+    pub fn list(&self, input: ListInput) -> Result<ListResponse, OperationError> {
+        let limit = input.limit.unwrap_or(DEFAULT_LIMIT);
+
+        if limit == 0 || limit > MAX_LIMIT || (input.offset.is_some() && input.cursor.is_some()) {
+            return Err(OperationError::BadParameters);
+        }
+
+        let filter = input.filter.unwrap_or_default();
+        let matches: Vec<CanisterMetadata> = self
+            .0
+            .iter()
+            .map(|(_, canister)| canister)
+            .filter(|canister| filter.matches(canister))
+            .collect();
+        let total = matches.len();
+
+        // Entries are ordered by principal id, so a cursor is the principal of the last seen entry
+        let start = match input.cursor {
+            Some(cursor) => matches.partition_point(|canister| canister.principal_id <= cursor),
+            None => input.offset.unwrap_or(0).min(total),
+        };
+
+        let end = (start + limit).min(total);
+        let items: Vec<CanisterMetadata> = matches[start..end].to_vec();
+        let next_cursor = match end < total {
+            true => items.last().map(|canister| canister.principal_id),
+            false => None,
+        };
+
+        Ok(ListResponse {
+            items,
+            total,
+            next_cursor,
+        })
     }
 }
 
 #[init]
-pub fn init() {
+pub fn init(config: Option<Config>) {
     ic::store(Admins(vec![ic::caller()]));
+    configure(config);
+    ic::get::<CanisterDB>().certify();
 }
 
 #[query]
@@ -106,32 +324,127 @@ fn name() -> String {
     String::from("Canister Registry")
 }
 
+// The certificate is only available in query calls, the witness proves the entry or its absence
 #[query]
-pub fn get(canister: Principal) -> Option<&'static CanisterMetadata> {
+pub fn get(canister: Principal) -> CertifiedResponse {
+    let canister_db = ic::get::<CanisterDB>();
+
+    CertifiedResponse {
+        metadata: canister_db.get_info(canister),
+        certificate: ic::data_certificate(),
+        witness: encode_tree(&canister_db.3.witness(&canister)),
+    }
+}
+
+pub fn validate_input(metadata: &AddCanisterInput) -> Result<(), OperationError> {
+    let config = ic::get::<Config>();
+
+    if &metadata.name.len() > &config.name_limit
+        || &metadata.description.len() > &config.description_limit
+        || metadata.thumbnail.len() > config.url_limit
+        || metadata.frontend.as_ref().map_or(0, String::len) > config.url_limit
+        || !validate_url(&metadata.thumbnail)
+        || !metadata.clone().frontend.map(validate_url).unwrap_or(true)
+    {
+        return Err(OperationError::BadParameters);
+    }
+
+    // The details start with a category registered by an admin, followed by the keys the config allows
+    let (other_details, category) = match metadata.details.split_first() {
+        Some(((key, DetailValue::Text(category)), other_details)) if key == CATEGORY_KEY => {
+            (other_details, category)
+        }
+        _ => return Err(OperationError::BadParameters),
+    };
+
+    if !ic::get::<Categories>().contains(category) {
+        return Err(OperationError::BadParameters);
+    }
+
+    for (index, (key, _)) in other_details.iter().enumerate() {
+        if !config.is_allowed_detail(key)
+            || other_details[..index].iter().any(|(other, _)| other == key)
+        {
+            return Err(OperationError::BadParameters);
+        }
+    }
+
+    // The stored entry has to fit in its stable memory slot
+    match encode_one(metadata) {
+        Ok(bytes) if bytes.len() <= MAX_INPUT_SIZE => Ok(()),
+        _ => Err(OperationError::BadParameters),
+    }
+}
+
+// Entries that look like an impersonation or a copy of another entry wait for an admin's review.
+// The entries are decoded from stable memory once per call and passed along, a batch keeps them
+// up to date as its items are published.
+fn publish(
+    caller: &Principal,
+    metadata: AddCanisterInput,
+    published: &mut Vec<CanisterMetadata>,
+) -> Result<Option<String>, OperationError> {
     let canister_db = ic::get_mut::<CanisterDB>();
-    canister_db.get_info(canister)
+    canister_db.check_authorization(caller, &metadata.principal_id)?;
+
+    let flags = detect_flags(published.iter(), &metadata);
+    if !flags.is_empty() {
+        // The admins are not limited, e.g. the NFT and token registries that list their entries here
+        let queue = ic::get_mut::<ReviewQueue>();
+        if !is_admin(&ic::caller()) {
+            queue.check_capacity(caller, &metadata.principal_id)?;
+        }
+        queue.push(caller, metadata, flags);
+        return Ok(Some(String::from("The entry has been flagged for review")));
+    }
+
+    let principal_id = metadata.principal_id;
+    canister_db.add_canister(caller, metadata)?;
+
+    published.retain(|canister| canister.principal_id != principal_id);
+    published.extend(canister_db.get_info(principal_id));
+    Ok(None)
 }
 
 #[update]
 pub fn add(
     trusted_source: Option<Principal>,
     metadata: AddCanisterInput,
-) -> Result<(), OperationError> {
+) -> Result<Option<String>, OperationError> {
     let caller = ic::caller();
 
     if !is_admin(&caller) {
         return Err(OperationError::NotAuthorized);
-    } else if &metadata.name.len() > &NAME_LIMIT
-        || &metadata.description.len() > &DESCRIPTION_LIMIT
-        || !validate_url(&metadata.thumbnail)
-        || !metadata.clone().frontend.map(validate_url).unwrap_or(true)
-        || (metadata.details.len() != 1 && metadata.details[0].0 != String::from("category"))
-    {
-        return Err(OperationError::BadParameters);
     }
+    validate_input(&metadata)?;
 
-    let canister_db = ic::get_mut::<CanisterDB>();
-    canister_db.add_canister(&trusted_source.unwrap_or(caller), metadata)
+    let mut published = ic::get::<CanisterDB>().get_all();
+    publish(&trusted_source.unwrap_or(caller), metadata, &mut published)
+}
+
+pub async fn submit_with<L: ControllerLookup>(
+    lookup: &L,
+    metadata: AddCanisterInput,
+) -> Result<Option<String>, OperationError> {
+    let caller = ic::caller();
+
+    if caller == Principal::anonymous() {
+        return Err(OperationError::NotAuthorized);
+    }
+    validate_input(&metadata)?;
+
+    // Only one of the canister's controllers can list it
+    if !is_controller(lookup, &caller, metadata.principal_id).await? {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    let mut published = ic::get::<CanisterDB>().get_all();
+    publish(&caller, metadata, &mut published)
+}
+
+#[update]
+pub async fn submit(metadata: AddCanisterInput) -> Result<Option<String>, OperationError> {
+    submit_with(&ManagementCanister, metadata).await
 }
 
 #[update]
@@ -147,8 +460,91 @@ pub fn remove(
     canister_db.remove_canister(&trusted_source.unwrap_or(caller), &canister)
 }
 
+#[update]
+pub fn add_batch(
+    trusted_source: Option<Principal>,
+    canisters: Vec<AddCanisterInput>,
+) -> Result<Vec<BatchResult>, OperationError> {
+    let caller = ic::caller();
+
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    } else if canisters.len() > MAX_BATCH_SIZE {
+        return Err(OperationError::BadParameters);
+    }
+
+    // A bad item is reported in its own result and does not stop the rest of the batch
+    let mut published = ic::get::<CanisterDB>().get_all();
+    let results = canisters
+        .into_iter()
+        .map(|metadata| BatchResult {
+            principal_id: metadata.principal_id,
+            result: validate_input(&metadata)
+                .and_then(|_| publish(&trusted_source.unwrap_or(caller), metadata, &mut published)),
+        })
+        .collect();
+
+    Ok(results)
+}
+
+#[update]
+pub fn remove_batch(
+    trusted_source: Option<Principal>,
+    canisters: Vec<Principal>,
+) -> Result<Vec<BatchResult>, OperationError> {
+    let caller = ic::caller();
+
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    } else if canisters.len() > MAX_BATCH_SIZE {
+        return Err(OperationError::BadParameters);
+    }
+
+    let canister_db = ic::get_mut::<CanisterDB>();
+    let results = canisters
+        .into_iter()
+        .map(|principal_id| BatchResult {
+            principal_id,
+            result: canister_db
+                .remove_canister(&trusted_source.unwrap_or(caller), &principal_id)
+                .map(|_| None),
+        })
+        .collect();
+
+    Ok(results)
+}
+
 #[query]
-pub fn get_all() -> Vec<&'static CanisterMetadata> {
+pub fn get_revisions(canister: Principal) -> Vec<CanisterRevision> {
+    let canister_db = ic::get::<CanisterDB>();
+    canister_db.get_revisions(&canister)
+}
+
+#[update]
+pub fn revert(canister: Principal, revision: u64) -> Result<(), OperationError> {
+    let caller = ic::caller();
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    }
+
     let canister_db = ic::get_mut::<CanisterDB>();
+    canister_db.revert(&caller, &canister, revision)
+}
+
+#[query]
+pub fn get_all() -> Vec<CanisterMetadata> {
+    let canister_db = ic::get::<CanisterDB>();
     canister_db.get_all()
 }
+
+#[query]
+pub fn search(query: String, limit: Option<usize>) -> Vec<CanisterMetadata> {
+    let canister_db = ic::get::<CanisterDB>();
+    canister_db.search(&query, limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+}
+
+#[query]
+pub fn list(input: ListInput) -> Result<ListResponse, OperationError> {
+    let canister_db = ic::get::<CanisterDB>();
+    canister_db.list(input)
+}
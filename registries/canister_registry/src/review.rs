@@ -0,0 +1,192 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::collections::BTreeMap;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+use crate::registry::{validate_input, CanisterDB};
+
+// Flagged submissions that the controllers can queue, so that the queue cannot grow without bound
+pub const MAX_PENDING_PER_SUBMITTER: usize = 5;
+pub const MAX_PENDING_ENTRIES: usize = 500;
+
+// Names with at least this many characters are also flagged when they are one edit away
+pub const SIMILAR_NAME_MIN_LENGTH: usize = 5;
+
+// Reduces a name to a form where look-alike characters compare equal, e.g. "DSCVR" and "Dscvr",
+// or "ICPunks" and "lCPunks"
+pub fn skeleton(name: &str) -> String {
+    name.to_lowercase()
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | 'i' => 'l',
+            '3' => 'e',
+            '5' => 's',
+            _ => c,
+        })
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+pub fn is_similar_name(a: &str, b: &str) -> bool {
+    let (a, b) = (skeleton(a), skeleton(b));
+
+    if a == b {
+        return true;
+    }
+
+    a.chars().count() >= SIMILAR_NAME_MIN_LENGTH
+        && b.chars().count() >= SIMILAR_NAME_MIN_LENGTH
+        && edit_distance(&a, &b) <= 1
+}
+
+// Compares a submission against the other entries of the registry
+pub fn detect_flags<'a>(
+    canisters: impl Iterator<Item = &'a CanisterMetadata>,
+    metadata: &AddCanisterInput,
+) -> Vec<Flag> {
+    let mut flags = vec![];
+
+    for canister in canisters.filter(|canister| canister.principal_id != metadata.principal_id) {
+        if is_similar_name(&canister.name, &metadata.name) {
+            flags.push(Flag::SimilarName(canister.principal_id));
+        }
+
+        if canister.thumbnail == metadata.thumbnail {
+            flags.push(Flag::DuplicateThumbnail(canister.principal_id));
+        }
+
+        if canister.frontend.is_some() && canister.frontend == metadata.frontend {
+            flags.push(Flag::DuplicateFrontend(canister.principal_id));
+        }
+
+        if canister.description == metadata.description {
+            flags.push(Flag::DuplicateDescription(canister.principal_id));
+        }
+    }
+
+    flags
+}
+
+// Flagged submissions waiting for an admin, keyed by the principal id of the submitted canister
+#[derive(Default)]
+pub struct ReviewQueue(BTreeMap<Principal, PendingEntry>);
+
+impl ReviewQueue {
+    pub fn archive(&mut self) -> Vec<PendingEntry> {
+        let map = std::mem::take(&mut self.0);
+        map.into_values().collect()
+    }
+
+    pub fn load(&mut self, archive: Vec<PendingEntry>) {
+        self.0 = archive
+            .into_iter()
+            .map(|entry| (entry.metadata.principal_id, entry))
+            .collect();
+    }
+
+    pub fn push(&mut self, submitter: &Principal, metadata: AddCanisterInput, flags: Vec<Flag>) {
+        let entry = PendingEntry {
+            submitter: *submitter,
+            submitted_at: ic::time(),
+            metadata,
+            flags,
+        };
+
+        self.0.insert(entry.metadata.principal_id, entry);
+    }
+
+    // A new submission of a queued canister replaces it and does not take another place
+    pub fn check_capacity(
+        &self,
+        submitter: &Principal,
+        principal_id: &Principal,
+    ) -> Result<(), OperationError> {
+        if self.0.contains_key(principal_id) {
+            return Ok(());
+        }
+
+        let own = self
+            .0
+            .values()
+            .filter(|entry| entry.submitter == *submitter)
+            .count();
+        if own >= MAX_PENDING_PER_SUBMITTER || self.0.len() >= MAX_PENDING_ENTRIES {
+            return Err(OperationError::BadParameters);
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, principal_id: &Principal) -> Option<&PendingEntry> {
+        self.0.get(principal_id)
+    }
+
+    pub fn take(&mut self, principal_id: &Principal) -> Result<PendingEntry, OperationError> {
+        self.0
+            .remove(principal_id)
+            .ok_or(OperationError::NonExistentItem)
+    }
+
+    pub fn get_all(&self) -> Vec<&PendingEntry> {
+        self.0.values().collect()
+    }
+}
+
+#[query]
+pub fn get_review_queue() -> Result<Vec<&'static PendingEntry>, OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    Ok(ic::get::<ReviewQueue>().get_all())
+}
+
+#[update]
+pub fn accept_entry(canister: Principal) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    let queue = ic::get_mut::<ReviewQueue>();
+    let entry = queue
+        .get(&canister)
+        .cloned()
+        .ok_or(OperationError::NonExistentItem)?;
+
+    // The category may have been removed, or the config changed, while the entry was waiting
+    validate_input(&entry.metadata)?;
+    ic::get_mut::<CanisterDB>().add_canister(&entry.submitter, entry.metadata)?;
+    queue.take(&canister).map(|_| ())
+}
+
+#[update]
+pub fn reject_entry(canister: Principal) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    ic::get_mut::<ReviewQueue>().take(&canister).map(|_| ())
+}
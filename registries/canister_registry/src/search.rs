@@ -0,0 +1,84 @@
+use ic_kit::candid::Principal;
+use ic_stable_structures::StableBTreeMap;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ops::Bound::{Included, Unbounded};
+
+use crate::common_types::CanisterMetadata;
+use crate::memory::{get_memory, Memory, StablePrincipal, Token, SEARCH_INDEX};
+
+pub const NAME_WEIGHT: u32 = 3;
+pub const DESCRIPTION_WEIGHT: u32 = 1;
+
+// ((token, canister) -> weight)
+pub struct SearchIndex(StableBTreeMap<(Token, StablePrincipal), u32, Memory>);
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self(StableBTreeMap::init(get_memory(SEARCH_INDEX)))
+    }
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| Token::new(&token.to_lowercase()).0)
+        .collect()
+}
+
+impl SearchIndex {
+    fn weighted_tokens(canister: &CanisterMetadata) -> BTreeMap<String, u32> {
+        let mut tokens = BTreeMap::new();
+
+        for token in tokenize(&canister.name) {
+            *tokens.entry(token).or_insert(0) += NAME_WEIGHT;
+        }
+
+        for token in tokenize(&canister.description) {
+            *tokens.entry(token).or_insert(0) += DESCRIPTION_WEIGHT;
+        }
+
+        tokens
+    }
+
+    pub fn insert(&mut self, canister: &CanisterMetadata) {
+        for (token, weight) in Self::weighted_tokens(canister) {
+            self.0.insert(
+                (Token(token), StablePrincipal(canister.principal_id)),
+                weight,
+            );
+        }
+    }
+
+    pub fn remove(&mut self, canister: &CanisterMetadata) {
+        for token in Self::weighted_tokens(canister).into_keys() {
+            self.0
+                .remove(&(Token(token), StablePrincipal(canister.principal_id)));
+        }
+    }
+
+    // Returns the matching canisters ranked by relevance. Exact token matches score twice as
+    // much as prefix matches, so "dra" finds "dragon" but "dragon" ranks exact hits first.
+    pub fn search(&self, query: &str) -> Vec<(Principal, u32)> {
+        let mut scores: BTreeMap<Principal, u32> = BTreeMap::new();
+
+        for query_token in tokenize(query) {
+            // The default principal id is the smallest one, so the range starts at the first key of the token
+            let start = (Token(query_token.clone()), StablePrincipal::default());
+
+            for ((token, principal_id), weight) in self
+                .0
+                .range((Included(start), Unbounded))
+                .take_while(|((token, _), _)| token.0.starts_with(&query_token))
+            {
+                let multiplier = if token.0 == query_token { 2 } else { 1 };
+                *scores.entry(principal_id.0).or_insert(0) += weight * multiplier;
+            }
+        }
+
+        let mut ranked: Vec<(Principal, u32)> = scores.into_iter().collect();
+        // Sorting is stable, so equal scores stay ordered by principal id
+        ranked.sort_by_key(|(_, score)| Reverse(*score));
+        ranked
+    }
+}
@@ -494,3 +494,1031 @@
 //         assert_eq!(remove_operation.err().unwrap(), Failure::NotAuthorized);
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use crate::categories::{add_category, get_by_category, remove_category};
+    use crate::certified::{entry_hash, CertifiedEntries};
+    use crate::common_types::*;
+    use crate::config::{get_config, set_config};
+    use crate::controllers::{ControllerLookup, ControllersResponse};
+    use crate::http::{csv_field, http_request, CSV_HEADER};
+    use crate::memory::{StablePrincipal, Token, MAX_METADATA_SIZE, MAX_TOKEN_SIZE};
+    use crate::registry::{
+        add, add_batch, get, get_revisions, init, list, remove, remove_batch, revert, search,
+        submit_with,
+    };
+    use crate::review::{
+        accept_entry, get_review_queue, is_similar_name, reject_entry, MAX_PENDING_PER_SUBMITTER,
+    };
+    use crate::search::tokenize;
+    use crate::upgrade::{migrate, StableStorage};
+    use ic_certified_map::HashTree;
+    use ic_kit::candid::{decode_one, encode_one, CandidType};
+    use ic_kit::*;
+    use ic_stable_structures::Storable;
+
+    // Every canister gets its own name, description and URLs so that they are not flagged for review
+    fn canister_input(principal_id: Principal, category: &str) -> AddCanisterInput {
+        let id = principal_id.to_text();
+
+        AddCanisterInput {
+            name: id[..NAME_LIMIT].to_string(),
+            description: format!("{} is a canister.", id),
+            thumbnail: format!("https://logo_url.com/{}.png", id),
+            frontend: Some(format!("https://{}.ic0.app", id)),
+            principal_id,
+            details: vec![(
+                String::from("category"),
+                DetailValue::Text(String::from(category)),
+            )],
+        }
+    }
+
+    fn init_with_categories() {
+        init(None);
+
+        for name in ["Service", "Tools"] {
+            let category = Category {
+                name: String::from(name),
+                description: String::new(),
+                parent: None,
+            };
+            assert!(add_category(category).is_ok());
+        }
+    }
+
+    fn canister_metadata(principal_id: Principal) -> CanisterMetadata {
+        let input = canister_input(principal_id, "Service");
+
+        CanisterMetadata {
+            name: input.name,
+            description: input.description,
+            thumbnail: input.thumbnail,
+            frontend: input.frontend,
+            principal_id,
+            submitter: mock_principals::alice(),
+            last_updated_by: mock_principals::alice(),
+            last_updated_at: 0,
+            details: input.details,
+        }
+    }
+
+    fn contains_leaf(tree: &HashTree, hash: &[u8]) -> bool {
+        match tree {
+            HashTree::Leaf(bytes) => bytes.as_ref() == hash,
+            HashTree::Labeled(_, subtree) => contains_leaf(subtree, hash),
+            HashTree::Fork(forks) => contains_leaf(&forks.0, hash) || contains_leaf(&forks.1, hash),
+            _ => false,
+        }
+    }
+
+    // Answers controller lookups without calling the management canister, `None` simulates a rejected call
+    struct MockLookup(Option<Vec<Principal>>);
+
+    impl ControllerLookup for MockLookup {
+        fn controllers(&self, _canister: Principal) -> ControllersResponse {
+            let controllers = self.0.clone();
+            Box::pin(async move {
+                controllers.ok_or(OperationError::Unknown(String::from("call rejected")))
+            })
+        }
+    }
+
+    fn http_get(url: &str) -> HttpResponse {
+        http_request(HttpRequest {
+            method: String::from("GET"),
+            url: String::from(url),
+            headers: vec![],
+            body: vec![],
+        })
+    }
+
+    // The layout of the registry before the stable structures, with free text categories
+    #[derive(CandidType)]
+    struct BaselineStorage {
+        db: Vec<(Principal, CanisterMetadata)>,
+        admins: Vec<Principal>,
+    }
+
+    #[test]
+    fn test_list_pages_through_the_registry_in_order() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let canisters = vec![
+            mock_principals::xtc(),
+            mock_principals::alice(),
+            mock_principals::bob(),
+            mock_principals::john(),
+        ];
+        for canister in canisters.iter() {
+            assert!(add(None, canister_input(*canister, "Service")).is_ok());
+        }
+
+        let first_page = list(ListInput {
+            limit: Some(3),
+            ..ListInput::default()
+        })
+        .unwrap();
+        assert_eq!(first_page.total, 4);
+        assert_eq!(first_page.items.len(), 3);
+        assert!(first_page.next_cursor.is_some());
+
+        let second_page = list(ListInput {
+            limit: Some(3),
+            cursor: first_page.next_cursor,
+            ..ListInput::default()
+        })
+        .unwrap();
+        assert_eq!(second_page.items.len(), 1);
+        assert_eq!(second_page.next_cursor, None);
+
+        let mut sorted = canisters.clone();
+        sorted.sort();
+        let listed: Vec<Principal> = first_page
+            .items
+            .iter()
+            .chain(second_page.items.iter())
+            .map(|canister| canister.principal_id)
+            .collect();
+        assert_eq!(listed, sorted);
+
+        let offset_page = list(ListInput {
+            offset: Some(3),
+            limit: Some(3),
+            ..ListInput::default()
+        })
+        .unwrap();
+        assert_eq!(offset_page.items, second_page.items);
+    }
+
+    #[test]
+    fn test_list_applies_filters() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        assert!(add(None, canister_input(mock_principals::xtc(), "Service")).is_ok());
+        assert!(add(None, canister_input(mock_principals::bob(), "NFT")).is_ok());
+        assert!(add(
+            Some(mock_principals::john()),
+            canister_input(mock_principals::john(), "NFT")
+        )
+        .is_ok());
+
+        let nfts = list(ListInput {
+            filter: Some(ListFilter {
+                category: Some(String::from("NFT")),
+                ..ListFilter::default()
+            }),
+            ..ListInput::default()
+        })
+        .unwrap();
+        assert_eq!(nfts.total, 2);
+
+        let submitted_by_john = list(ListInput {
+            filter: Some(ListFilter {
+                category: Some(String::from("NFT")),
+                submitter: Some(mock_principals::john()),
+                ..ListFilter::default()
+            }),
+            ..ListInput::default()
+        })
+        .unwrap();
+        assert_eq!(submitted_by_john.total, 1);
+        assert_eq!(
+            submitted_by_john.items[0].principal_id,
+            mock_principals::john()
+        );
+
+        let updated_in_the_future = list(ListInput {
+            filter: Some(ListFilter {
+                updated_since: Some(u64::MAX),
+                ..ListFilter::default()
+            }),
+            ..ListInput::default()
+        })
+        .unwrap();
+        assert_eq!(updated_in_the_future.total, 0);
+    }
+
+    #[test]
+    fn test_list_rejects_bad_parameters() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let over_limit = list(ListInput {
+            limit: Some(MAX_LIMIT + 1),
+            ..ListInput::default()
+        });
+        assert_eq!(over_limit.unwrap_err(), OperationError::BadParameters);
+
+        let offset_and_cursor = list(ListInput {
+            offset: Some(0),
+            cursor: Some(mock_principals::xtc()),
+            ..ListInput::default()
+        });
+        assert_eq!(
+            offset_and_cursor.unwrap_err(),
+            OperationError::BadParameters
+        );
+    }
+
+    #[test]
+    fn test_tokenize_splits_and_lowercases() {
+        assert_eq!(
+            tokenize("Cycles Token (XTC) - wallet's"),
+            vec!["cycles", "token", "xtc", "wallet", "s"]
+        );
+    }
+
+    #[test]
+    fn test_stable_keys_and_values_round_trip() {
+        let token = Token::new(&"é".repeat(MAX_TOKEN_SIZE));
+        assert_eq!(token.0.len(), MAX_TOKEN_SIZE);
+        assert_eq!(Token::from_bytes(token.to_bytes()).0, token.0);
+
+        let principal_id = StablePrincipal(mock_principals::xtc());
+        assert_eq!(
+            StablePrincipal::from_bytes(principal_id.to_bytes()).0,
+            mock_principals::xtc()
+        );
+
+        let metadata = canister_metadata(mock_principals::xtc());
+        let decoded = CanisterMetadata::from_bytes(metadata.to_bytes());
+        assert_eq!(decoded.name, metadata.name);
+        assert_eq!(decoded.details.len(), 1);
+    }
+
+    #[test]
+    fn test_search_ranks_name_matches_first() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let mut xtc = canister_input(mock_principals::xtc(), "Service");
+        xtc.name = String::from("XTC");
+        xtc.description = String::from("Cycles token wallet");
+        assert!(add(None, xtc).is_ok());
+
+        let mut cycles = canister_input(mock_principals::bob(), "Service");
+        cycles.name = String::from("Cycles Minting");
+        cycles.description = String::from("Mints cycles from ICP");
+        assert!(add(None, cycles).is_ok());
+
+        let results = search(String::from("cycles"), None);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].principal_id, mock_principals::bob());
+        assert_eq!(results[1].principal_id, mock_principals::xtc());
+
+        // Prefix matches are found as well
+        let results = search(String::from("wall"), None);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].principal_id, mock_principals::xtc());
+
+        let results = search(String::from("cycles"), Some(1));
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_search_index_follows_updates_and_removals() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let mut xtc = canister_input(mock_principals::xtc(), "Service");
+        xtc.description = String::from("Cycles token wallet");
+        assert!(add(None, xtc.clone()).is_ok());
+
+        xtc.description = String::from("Wrapped cycles");
+        assert!(add(None, xtc).is_ok());
+        assert!(search(String::from("token"), None).is_empty());
+        assert_eq!(search(String::from("wrapped"), None).len(), 1);
+
+        assert!(remove(None, mock_principals::xtc()).is_ok());
+        assert!(search(String::from("wrapped"), None).is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_controller_can_submit_their_canister() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        ctx.update_caller(mock_principals::bob());
+        let lookup = MockLookup(Some(vec![mock_principals::john(), mock_principals::bob()]));
+        let submission =
+            submit_with(&lookup, canister_input(mock_principals::xtc(), "Service")).await;
+        assert!(submission.is_ok());
+
+        let canister = get(mock_principals::xtc()).metadata.unwrap();
+        assert_eq!(canister.submitter, mock_principals::bob());
+
+        // The submitter can keep their entry up to date
+        let submission =
+            submit_with(&lookup, canister_input(mock_principals::xtc(), "Tools")).await;
+        assert!(submission.is_ok());
+    }
+
+    #[async_std::test]
+    async fn test_flagged_submissions_are_capped_per_submitter() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+        let xtc = canister_input(mock_principals::xtc(), "Service");
+        assert!(add(None, xtc.clone()).is_ok());
+
+        // Every submission reuses the thumbnail of xtc and is flagged
+        let copy = |seed: u8| {
+            let mut input = canister_input(Principal::from_slice(&[seed; 10]), "Service");
+            input.thumbnail = xtc.thumbnail.clone();
+            input
+        };
+
+        ctx.update_caller(mock_principals::bob());
+        let lookup = MockLookup(Some(vec![mock_principals::bob()]));
+        for seed in 0..MAX_PENDING_PER_SUBMITTER as u8 {
+            assert!(submit_with(&lookup, copy(seed)).await.unwrap().is_some());
+        }
+        assert_eq!(
+            submit_with(&lookup, copy(0xff)).await,
+            Err(OperationError::BadParameters)
+        );
+        // A queued canister can still be submitted again
+        assert!(submit_with(&lookup, copy(0)).await.unwrap().is_some());
+
+        // The admins are not limited
+        ctx.update_caller(mock_principals::alice());
+        assert!(add(Some(mock_principals::john()), copy(0xff))
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            get_review_queue().unwrap().len(),
+            MAX_PENDING_PER_SUBMITTER + 1
+        );
+
+        // A review frees a place
+        assert!(reject_entry(Principal::from_slice(&[0; 10])).is_ok());
+        ctx.update_caller(mock_principals::bob());
+        assert!(submit_with(&lookup, copy(0xfe)).await.unwrap().is_some());
+    }
+
+    #[async_std::test]
+    async fn test_submission_is_rejected_for_non_controllers() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        ctx.update_caller(mock_principals::bob());
+        let lookup = MockLookup(Some(vec![mock_principals::john()]));
+        let submission =
+            submit_with(&lookup, canister_input(mock_principals::xtc(), "Service")).await;
+        assert_eq!(submission.unwrap_err(), OperationError::NotAuthorized);
+        assert!(get(mock_principals::xtc()).metadata.is_none());
+
+        let lookup = MockLookup(None);
+        let submission =
+            submit_with(&lookup, canister_input(mock_principals::xtc(), "Service")).await;
+        assert_eq!(
+            submission.unwrap_err(),
+            OperationError::Unknown(String::from("call rejected"))
+        );
+        assert!(get(mock_principals::xtc()).metadata.is_none());
+    }
+
+    #[test]
+    fn test_add_rejects_unregistered_or_malformed_categories() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let addition = add(None, canister_input(mock_principals::xtc(), "Games"));
+        assert_eq!(addition.unwrap_err(), OperationError::BadParameters);
+
+        let mut input = canister_input(mock_principals::xtc(), "Service");
+        input.details = vec![];
+        assert_eq!(
+            add(None, input.clone()).unwrap_err(),
+            OperationError::BadParameters
+        );
+
+        input.details = vec![(
+            String::from("standard"),
+            DetailValue::Text(String::from("Service")),
+        )];
+        assert_eq!(
+            add(None, input.clone()).unwrap_err(),
+            OperationError::BadParameters
+        );
+
+        // The categories injected by the NFT and token registries are registered by default
+        assert!(add(None, canister_input(mock_principals::xtc(), "Token")).is_ok());
+        assert!(add(None, canister_input(mock_principals::bob(), "NFT")).is_ok());
+    }
+
+    #[test]
+    fn test_get_by_category_includes_subcategories() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let games = Category {
+            name: String::from("Games"),
+            description: String::from("Games built on the IC"),
+            parent: Some(String::from("Service")),
+        };
+        assert!(add_category(games).is_ok());
+
+        assert!(add(None, canister_input(mock_principals::xtc(), "Service")).is_ok());
+        assert!(add(None, canister_input(mock_principals::bob(), "Games")).is_ok());
+        assert!(add(None, canister_input(mock_principals::john(), "Tools")).is_ok());
+
+        assert_eq!(get_by_category(String::from("Service")).unwrap().len(), 2);
+        assert_eq!(get_by_category(String::from("Games")).unwrap().len(), 1);
+        assert_eq!(
+            get_by_category(String::from("DeFi")).unwrap_err(),
+            OperationError::NonExistentItem
+        );
+
+        // A category can not become its own ancestor
+        let cycle = Category {
+            name: String::from("Service"),
+            description: String::new(),
+            parent: Some(String::from("Games")),
+        };
+        assert_eq!(
+            add_category(cycle).unwrap_err(),
+            OperationError::BadParameters
+        );
+
+        // Categories that are in use, have children or are built in can not be removed
+        assert_eq!(
+            remove_category(String::from("Service")).unwrap_err(),
+            OperationError::BadParameters
+        );
+        assert_eq!(
+            remove_category(String::from("NFT")).unwrap_err(),
+            OperationError::BadParameters
+        );
+        assert!(remove(None, mock_principals::john()).is_ok());
+        assert!(remove_category(String::from("Tools")).is_ok());
+
+        ctx.update_caller(mock_principals::bob());
+        let category = Category {
+            name: String::from("DeFi"),
+            description: String::new(),
+            parent: None,
+        };
+        assert_eq!(
+            add_category(category).unwrap_err(),
+            OperationError::NotAuthorized
+        );
+    }
+
+    #[test]
+    fn test_certified_tree_witnesses_reconstruct_the_root() {
+        let mut certified = CertifiedEntries::default();
+        let empty_root = certified.root_hash();
+
+        let xtc = canister_metadata(mock_principals::xtc());
+        let bob = canister_metadata(mock_principals::bob());
+        certified.insert(&xtc);
+        certified.insert(&bob);
+        let root = certified.root_hash();
+        assert_ne!(root, empty_root);
+
+        // A witness proves the entry's hash, or the absence of the entry, against the same root
+        let witness = certified.witness(&mock_principals::xtc());
+        assert_eq!(witness.reconstruct(), root);
+        assert!(contains_leaf(&witness, &entry_hash(&xtc)));
+        assert!(!contains_leaf(&witness, &entry_hash(&bob)));
+
+        let witness = certified.witness(&mock_principals::john());
+        assert_eq!(witness.reconstruct(), root);
+        assert!(!contains_leaf(&witness, &entry_hash(&xtc)));
+
+        // Updating an entry changes the root, removing every entry restores the empty root
+        let mut updated = xtc.clone();
+        updated.name = String::from("Phishing");
+        certified.insert(&updated);
+        assert_ne!(certified.root_hash(), root);
+
+        certified.remove(&mock_principals::xtc());
+        certified.remove(&mock_principals::bob());
+        assert_eq!(certified.root_hash(), empty_root);
+    }
+
+    #[test]
+    fn test_get_returns_certified_entries() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+        assert!(add(None, canister_input(mock_principals::xtc(), "Service")).is_ok());
+        assert!(add(None, canister_input(mock_principals::bob(), "Tools")).is_ok());
+
+        let response = get(mock_principals::xtc());
+        let metadata = response.metadata.unwrap();
+        assert!(response.certificate.is_some());
+        // The witness is self-describing CBOR that contains the hash of the returned entry
+        assert_eq!(response.witness[..3], [0xd9, 0xd9, 0xf7]);
+        assert!(response
+            .witness
+            .windows(32)
+            .any(|bytes| bytes == entry_hash(&metadata)));
+
+        // The certified data follows the adds and removes
+        let mut expected = CertifiedEntries::default();
+        expected.insert(&metadata);
+        expected.insert(&get(mock_principals::bob()).metadata.unwrap());
+        assert_eq!(
+            ctx.get_certified_data(),
+            Some(expected.root_hash().to_vec())
+        );
+
+        assert!(remove(None, mock_principals::bob()).is_ok());
+        expected.remove(&mock_principals::bob());
+        assert_eq!(
+            ctx.get_certified_data(),
+            Some(expected.root_hash().to_vec())
+        );
+        assert!(get(mock_principals::bob()).metadata.is_none());
+    }
+
+    #[test]
+    fn test_csv_fields_are_quoted_like_the_list() {
+        assert_eq!(csv_field("ICPunks"), "ICPunks");
+        assert_eq!(csv_field("10,000 clowns"), "\"10,000 clowns\"");
+        assert_eq!(csv_field("The \"XTC\" token"), "\"The \"\"XTC\"\" token\"");
+    }
+
+    #[test]
+    fn test_http_request_renders_the_registry() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+        let mut xtc = canister_input(mock_principals::xtc(), "Service");
+        xtc.description = String::from("Cycles, wrapped");
+        assert!(add(None, xtc.clone()).is_ok());
+
+        let response = http_get("/canisters.csv?refresh=1");
+        assert_eq!(response.status_code, 200);
+        let csv = String::from_utf8(response.body).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},{},\"Cycles, wrapped\",{},{}",
+                xtc.name,
+                mock_principals::xtc(),
+                xtc.frontend.unwrap(),
+                xtc.thumbnail
+            )
+        );
+
+        let response = http_get("/canisters.json");
+        assert_eq!(response.status_code, 200);
+        let json = String::from_utf8(response.body).unwrap();
+        assert!(json.starts_with(&format!("[{{\"name\":\"{}\"", xtc.name)));
+
+        let response = http_get(&format!("/canisters/{}.json", mock_principals::xtc()));
+        assert_eq!(response.status_code, 200);
+        assert!(String::from_utf8(response.body)
+            .unwrap()
+            .contains("\"description\":\"Cycles, wrapped\""));
+
+        assert_eq!(
+            http_get(&format!("/canisters/{}.json", mock_principals::bob())).status_code,
+            404
+        );
+        assert_eq!(http_get("/canisters/not-a-principal.json").status_code, 404);
+        assert_eq!(http_get("/").status_code, 404);
+    }
+
+    #[test]
+    fn test_revisions_are_kept_and_can_be_reverted() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let mut xtc = canister_input(mock_principals::xtc(), "Service");
+        assert!(add(None, xtc.clone()).is_ok());
+        assert!(get_revisions(mock_principals::xtc()).is_empty());
+
+        xtc.name = String::from("Phishing");
+        assert!(add(None, xtc.clone()).is_ok());
+
+        let revisions = get_revisions(mock_principals::xtc());
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].revision, 0);
+        assert_eq!(revisions[0].author, mock_principals::alice());
+        assert_ne!(revisions[0].metadata.name, "Phishing");
+
+        // Only admins can revert
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(
+            revert(mock_principals::xtc(), 0).unwrap_err(),
+            OperationError::NotAuthorized
+        );
+
+        ctx.update_caller(mock_principals::alice());
+        assert_eq!(
+            revert(mock_principals::xtc(), 5).unwrap_err(),
+            OperationError::NonExistentItem
+        );
+        assert!(revert(mock_principals::xtc(), 0).is_ok());
+        assert_eq!(
+            get(mock_principals::xtc()).metadata.unwrap().name,
+            revisions[0].metadata.name
+        );
+        assert_eq!(get_revisions(mock_principals::xtc()).len(), 2);
+        assert_eq!(search(String::from("phishing"), None).len(), 0);
+
+        // A removed entry can be brought back from its history
+        assert!(remove(None, mock_principals::xtc()).is_ok());
+        assert!(get(mock_principals::xtc()).metadata.is_none());
+        assert!(revert(mock_principals::xtc(), 2).is_ok());
+        assert!(get(mock_principals::xtc()).metadata.is_some());
+    }
+
+    #[test]
+    fn test_revert_validates_the_revision_again() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        // The category of the revision is removed after the entry moves to another one
+        let mut bob = canister_input(mock_principals::bob(), "Tools");
+        assert!(add(None, bob.clone()).is_ok());
+        bob.details[0].1 = DetailValue::Text(String::from("Service"));
+        assert!(add(None, bob).is_ok());
+        assert!(remove_category(String::from("Tools")).is_ok());
+
+        assert_eq!(
+            revert(mock_principals::bob(), 0).unwrap_err(),
+            OperationError::BadParameters
+        );
+    }
+
+    #[test]
+    fn test_similar_names() {
+        assert!(is_similar_name("ICPunks", "lCPunks"));
+        assert!(is_similar_name("DSCVR", "dscvr"));
+        assert!(is_similar_name("Distrikt", "Distrlkt"));
+        assert!(is_similar_name("Entrepot", "Entrep0t"));
+        assert!(is_similar_name("Modclub", "Rnodclub"));
+        assert!(is_similar_name("Dfinity", "Dfinlty!"));
+        assert!(!is_similar_name("XTC", "WTC"));
+        assert!(!is_similar_name("Sonic", "Plug"));
+    }
+
+    #[test]
+    fn test_flagged_entries_wait_for_review() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let original = canister_input(mock_principals::xtc(), "Service");
+        assert_eq!(add(None, original.clone()), Ok(None));
+
+        // Same description and a look-alike name
+        let mut copy = canister_input(mock_principals::bob(), "Service");
+        copy.name = original.name.replacen('a', "4", 1);
+        copy.description = original.description.clone();
+        assert!(add(None, copy.clone()).unwrap().is_some());
+        assert!(get(mock_principals::bob()).metadata.is_none());
+
+        let queue = get_review_queue().unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(
+            queue[0].flags,
+            vec![
+                Flag::SimilarName(mock_principals::xtc()),
+                Flag::DuplicateDescription(mock_principals::xtc())
+            ]
+        );
+
+        // A reused thumbnail is flagged as well
+        let mut reused = canister_input(mock_principals::john(), "Service");
+        reused.thumbnail = original.thumbnail.clone();
+        assert!(add(None, reused).unwrap().is_some());
+        assert_eq!(get_review_queue().unwrap().len(), 2);
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(
+            accept_entry(mock_principals::bob()).unwrap_err(),
+            OperationError::NotAuthorized
+        );
+        assert_eq!(
+            get_review_queue().unwrap_err(),
+            OperationError::NotAuthorized
+        );
+
+        ctx.update_caller(mock_principals::alice());
+        assert!(accept_entry(mock_principals::bob()).is_ok());
+        assert!(get(mock_principals::bob()).metadata.is_some());
+
+        assert!(reject_entry(mock_principals::john()).is_ok());
+        assert!(get(mock_principals::john()).metadata.is_none());
+        assert!(get_review_queue().unwrap().is_empty());
+        assert_eq!(
+            reject_entry(mock_principals::john()).unwrap_err(),
+            OperationError::NonExistentItem
+        );
+    }
+
+    #[test]
+    fn test_accept_validates_the_entry_again() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let original = canister_input(mock_principals::xtc(), "Service");
+        assert_eq!(add(None, original.clone()), Ok(None));
+        let mut reused = canister_input(mock_principals::john(), "Tools");
+        reused.thumbnail = original.thumbnail;
+        assert!(add(None, reused).unwrap().is_some());
+
+        // The category of a waiting entry can be removed, the entry cannot be accepted then
+        assert!(remove_category(String::from("Tools")).is_ok());
+        assert_eq!(
+            accept_entry(mock_principals::john()).unwrap_err(),
+            OperationError::BadParameters
+        );
+        assert!(get(mock_principals::john()).metadata.is_none());
+    }
+
+    #[test]
+    fn test_batch_calls_report_each_item() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+
+        let mut bad_row = canister_input(mock_principals::bob(), "Service");
+        bad_row.thumbnail = String::from("not a url");
+
+        let results = add_batch(
+            None,
+            vec![
+                canister_input(mock_principals::xtc(), "Service"),
+                bad_row,
+                canister_input(mock_principals::john(), "Tools"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].principal_id, mock_principals::xtc());
+        assert_eq!(results[0].result, Ok(None));
+        assert_eq!(results[1].result, Err(OperationError::BadParameters));
+        assert_eq!(results[2].result, Ok(None));
+        assert!(get(mock_principals::xtc()).metadata.is_some());
+        assert!(get(mock_principals::bob()).metadata.is_none());
+        assert!(get(mock_principals::john()).metadata.is_some());
+
+        let results =
+            remove_batch(None, vec![mock_principals::xtc(), mock_principals::bob()]).unwrap();
+        assert_eq!(results[0].result, Ok(None));
+        assert_eq!(results[1].result, Err(OperationError::NonExistentItem));
+        assert!(get(mock_principals::xtc()).metadata.is_none());
+
+        let oversized = vec![canister_input(mock_principals::xtc(), "Service"); MAX_BATCH_SIZE + 1];
+        assert_eq!(
+            add_batch(None, oversized).unwrap_err(),
+            OperationError::BadParameters
+        );
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(
+            remove_batch(None, vec![mock_principals::john()]).unwrap_err(),
+            OperationError::NotAuthorized
+        );
+    }
+
+    #[test]
+    fn test_batch_items_are_compared_with_each_other() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init_with_categories();
+        assert!(add(None, canister_input(mock_principals::xtc(), "Service")).is_ok());
+
+        // An item is compared with the published entries
+        let mut copy = canister_input(mock_principals::bob(), "Service");
+        copy.description = format!("{} is a canister.", mock_principals::xtc());
+        let results = add_batch(None, vec![copy]).unwrap();
+        assert!(results[0].result.as_ref().unwrap().is_some());
+        assert!(reject_entry(mock_principals::bob()).is_ok());
+
+        // and with the ones published earlier in the same batch
+        let first = canister_input(mock_principals::bob(), "Service");
+        let mut second = canister_input(mock_principals::john(), "Service");
+        second.thumbnail = first.thumbnail.clone();
+        let results = add_batch(None, vec![first, second]).unwrap();
+        assert_eq!(results[0].result, Ok(None));
+        assert!(results[1].result.as_ref().unwrap().is_some());
+        assert!(get(mock_principals::john()).metadata.is_none());
+    }
+
+    #[test]
+    fn test_config_sets_the_limits_and_detail_keys() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(Config {
+            name_limit: 64,
+            ..Config::default()
+        }));
+        assert_eq!(get_config().name_limit, 64);
+        assert!(add_category(Category {
+            name: String::from("Service"),
+            description: String::new(),
+            parent: None,
+        })
+        .is_ok());
+
+        let mut input = canister_input(mock_principals::xtc(), "Service");
+        input.name = mock_principals::xtc().to_text();
+        input.details.push((
+            String::from("twitter"),
+            DetailValue::Text(String::from("@xtc")),
+        ));
+        assert_eq!(
+            add(None, input.clone()).unwrap_err(),
+            OperationError::BadParameters
+        );
+
+        let config = Config {
+            allowed_detail_keys: vec![String::from("twitter")],
+            ..get_config().clone()
+        };
+        assert!(set_config(config.clone()).is_ok());
+        assert!(add(None, input.clone()).is_ok());
+
+        // The category has to come first and the keys cannot repeat
+        input.details.reverse();
+        assert_eq!(
+            add(None, input.clone()).unwrap_err(),
+            OperationError::BadParameters
+        );
+
+        let invalid = Config {
+            allowed_detail_keys: vec![String::from("category")],
+            ..config.clone()
+        };
+        assert_eq!(
+            set_config(invalid).unwrap_err(),
+            OperationError::BadParameters
+        );
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(
+            set_config(config).unwrap_err(),
+            OperationError::NotAuthorized
+        );
+    }
+
+    #[test]
+    fn test_baseline_storage_is_migrated() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        let mut xtc = canister_metadata(mock_principals::xtc());
+        xtc.description = String::from("Cycles token wallet");
+        let mut bob = canister_metadata(mock_principals::bob());
+        bob.details = vec![(
+            String::from("category"),
+            DetailValue::Text(String::from("Games")),
+        )];
+        let baseline = BaselineStorage {
+            db: vec![
+                (mock_principals::xtc(), xtc.clone()),
+                (mock_principals::bob(), bob.clone()),
+            ],
+            admins: vec![mock_principals::alice()],
+        };
+
+        let stable: StableStorage = decode_one(&encode_one(baseline).unwrap()).unwrap();
+        assert!(migrate(stable).is_ok());
+
+        assert_eq!(get(mock_principals::xtc()).metadata, Some(xtc.clone()));
+        assert_eq!(get(mock_principals::bob()).metadata, Some(bob.clone()));
+        assert_eq!(search(String::from("wallet"), None), vec![xtc.clone()]);
+        assert!(get_revisions(mock_principals::xtc()).is_empty());
+
+        let mut expected = CertifiedEntries::default();
+        expected.insert(&xtc);
+        expected.insert(&bob);
+        assert_eq!(
+            ctx.get_certified_data(),
+            Some(expected.root_hash().to_vec())
+        );
+
+        // The free text categories are registered, and the admins can change the migrated entries
+        assert_eq!(get_by_category(String::from("Games")).unwrap().len(), 1);
+        let mut input = canister_input(mock_principals::xtc(), "Games");
+        input.name = xtc.name.clone();
+        assert!(add(None, input).is_ok());
+        let revisions = get_revisions(mock_principals::xtc());
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].metadata, xtc);
+    }
+
+    #[test]
+    fn test_baseline_categories_are_kept_as_they_are() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        // Longer than the limit of the category names
+        let long_category = "Decentralized social networks";
+        assert!(long_category.len() > NAME_LIMIT);
+        let mut john = canister_metadata(mock_principals::john());
+        john.details = vec![(
+            String::from("category"),
+            DetailValue::Text(String::from(long_category)),
+        )];
+        let baseline = BaselineStorage {
+            db: vec![(mock_principals::john(), john.clone())],
+            admins: vec![mock_principals::alice()],
+        };
+
+        let stable: StableStorage = decode_one(&encode_one(baseline).unwrap()).unwrap();
+        assert!(migrate(stable).is_ok());
+
+        assert_eq!(
+            get_by_category(String::from(long_category)).unwrap(),
+            vec![john]
+        );
+        assert_eq!(
+            remove_category(String::from(long_category)),
+            Err(OperationError::BadParameters)
+        );
+    }
+
+    #[test]
+    fn test_oversized_baseline_entries_are_reported() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        // The baseline did not limit the thumbnails
+        let xtc = canister_metadata(mock_principals::xtc());
+        let mut bob = canister_metadata(mock_principals::bob());
+        bob.thumbnail = format!(
+            "https://logo.com/{}",
+            "a".repeat(MAX_METADATA_SIZE as usize)
+        );
+        let baseline = BaselineStorage {
+            db: vec![(mock_principals::xtc(), xtc), (mock_principals::bob(), bob)],
+            admins: vec![mock_principals::alice()],
+        };
+
+        let stable: StableStorage = decode_one(&encode_one(baseline).unwrap()).unwrap();
+        let err = migrate(stable).unwrap_err();
+        assert!(err.contains(&mock_principals::bob().to_text()));
+        assert!(!err.contains(&mock_principals::xtc().to_text()));
+        // Nothing is moved, so that the upgrade can be rolled back and the entry fixed
+        assert_eq!(get(mock_principals::xtc()).metadata, None);
+    }
+}
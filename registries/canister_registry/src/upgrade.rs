@@ -1,6 +1,9 @@
-use crate::common_types::{CanisterMetadata, DetailValue};
+use crate::categories::Categories;
+use crate::common_types::{CanisterMetadata, CanisterRevision, Category, Config, PendingEntry};
 use crate::management::Admins;
+use crate::memory::{is_legacy_layout, load_upgrade_state, save_upgrade_state};
 use crate::registry::CanisterDB;
+use crate::review::ReviewQueue;
 
 // IC imports
 use ic_cdk::export::candid::{CandidType, Deserialize, Principal};
@@ -8,25 +11,45 @@ use ic_kit::ic::*;
 use ic_kit::macros::*;
 use ic_kit::*;
 
+// The entries, revisions and search index live in stable structures, only the rest is saved on upgrades
 #[derive(CandidType, Deserialize)]
-struct StableStorage {
+struct UpgradeState {
+    admins: Vec<Principal>,
+    categories: Vec<Category>,
+    review_queue: Vec<PendingEntry>,
+    config: Option<Config>,
+}
+
+// The layout that was written with `stable_store` before the registry moved to stable structures
+#[derive(CandidType, Deserialize)]
+pub struct StableStorage {
     db: Vec<(Principal, CanisterMetadata)>,
     admins: Vec<Principal>,
+    categories: Option<Vec<Category>>,
+    revisions: Option<Vec<(Principal, Vec<CanisterRevision>)>>,
+    review_queue: Option<Vec<PendingEntry>>,
 }
 
 #[pre_upgrade]
 pub fn pre_upgrade() {
-    let db = ic::get_mut::<CanisterDB>().archive();
     let admins = ic::get_mut::<Admins>().0.clone();
+    let categories = ic::get_mut::<Categories>().archive();
+    let review_queue = ic::get_mut::<ReviewQueue>().archive();
+    let config = Some(ic::get::<Config>().clone());
 
-    let stable = StableStorage { db, admins };
+    let state = UpgradeState {
+        admins,
+        categories,
+        review_queue,
+        config,
+    };
 
-    match ic::stable_store((stable,)) {
+    match save_upgrade_state(&state) {
         Ok(_) => (),
-        Err(candid_err) => {
+        Err(err) => {
             trap(&format!(
                 "An error occurred when saving to stable memory (pre_upgrade): {:?}",
-                candid_err
+                err
             ));
         }
     };
@@ -34,8 +57,61 @@ pub fn pre_upgrade() {
 
 #[post_upgrade]
 pub fn post_upgrade() {
-    if let Ok((stable,)) = ic::stable_restore::<(StableStorage,)>() {
-        ic::get_mut::<CanisterDB>().load(stable.db);
-        ic::store(Admins(stable.admins));
+    // The legacy layout has to be read before the memory manager takes over the stable memory
+    // Trapping rolls the upgrade back, returning would let the memory manager overwrite the old layout
+    if is_legacy_layout() {
+        match ic::stable_restore::<(StableStorage,)>() {
+            Ok((stable,)) => {
+                if let Err(err) = migrate(stable) {
+                    trap(&format!(
+                        "An error occurred when migrating the legacy stable memory (post_upgrade): {}",
+                        err
+                    ));
+                }
+            }
+            Err(err) => trap(&format!(
+                "An error occurred when reading the legacy stable memory (post_upgrade): {}",
+                err
+            )),
+        }
+        return;
+    }
+
+    match load_upgrade_state::<UpgradeState>() {
+        Ok(Some(state)) => {
+            ic::store(Admins(state.admins));
+            ic::get_mut::<Categories>().load(state.categories);
+            ic::get_mut::<ReviewQueue>().load(state.review_queue);
+            ic::store(state.config.unwrap_or_default());
+        }
+        Ok(None) => (),
+        Err(err) => trap(&format!(
+            "An error occurred when reading from stable memory (post_upgrade): {}",
+            err
+        )),
+    }
+
+    // The certified tree only lives on the heap, it is rebuilt from the stable entries
+    ic::get::<CanisterDB>().certify();
+}
+
+pub fn migrate(stable: StableStorage) -> Result<(), String> {
+    ic::get_mut::<CanisterDB>().migrate(stable.db, stable.revisions.unwrap_or_default())?;
+    ic::store(Admins(stable.admins));
+    ic::get_mut::<ReviewQueue>().load(stable.review_queue.unwrap_or_default());
+
+    match stable.categories {
+        Some(categories) => ic::get_mut::<Categories>().load(categories),
+        // Register the categories of the existing entries when upgrading from the free text layout
+        None => {
+            let categories = ic::get_mut::<Categories>();
+            for canister in ic::get::<CanisterDB>().get_all() {
+                if let Some(name) = canister.category() {
+                    categories.insert_legacy(name);
+                }
+            }
+        }
     }
+
+    Ok(())
 }
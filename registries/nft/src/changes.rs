@@ -0,0 +1,148 @@
+use ic_kit::candid::{CandidType, Deserialize, Principal};
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::collections::VecDeque;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+
+pub const DEFAULT_RETENTION: u64 = 30 * 24 * 60 * 60; // 30 days, in seconds
+pub const DEFAULT_CHANGES_LIMIT: usize = 100;
+pub const MAX_CHANGES_LIMIT: usize = 500;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(CandidType, Deserialize)]
+pub struct ChangeFeedArchive {
+    changes: Vec<Change>,
+    next_sequence: u64,
+    pruned_until: u64,
+    retention: u64,
+}
+
+// The changes of the registry in order, sequence numbers start at 1 so that 0 means "nothing seen"
+pub struct ChangeFeed {
+    changes: VecDeque<Change>,
+    next_sequence: u64,
+    // The highest sequence number that was pruned
+    pruned_until: u64,
+    // How long changes are kept, in seconds
+    retention: u64,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self {
+            changes: VecDeque::new(),
+            next_sequence: 1,
+            pruned_until: 0,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+}
+
+impl ChangeFeed {
+    pub fn archive(&mut self) -> ChangeFeedArchive {
+        let feed = std::mem::take(self);
+
+        ChangeFeedArchive {
+            changes: feed.changes.into(),
+            next_sequence: feed.next_sequence,
+            pruned_until: feed.pruned_until,
+            retention: feed.retention,
+        }
+    }
+
+    pub fn load(&mut self, archive: ChangeFeedArchive) {
+        *self = Self {
+            changes: archive.changes.into(),
+            next_sequence: archive.next_sequence,
+            pruned_until: archive.pruned_until,
+            retention: archive.retention,
+        };
+    }
+
+    // The entries that existed before the feed count as a pruned change, so that the clients
+    // download them first
+    pub fn start_after_snapshot(&mut self) {
+        self.pruned_until = self.next_sequence;
+        self.next_sequence += 1;
+    }
+
+    pub fn latest_sequence(&self) -> u64 {
+        self.next_sequence - 1
+    }
+
+    pub fn record(&mut self, kind: ChangeKind, principal_id: Principal, nft: Option<NftCanister>) {
+        let change = Change {
+            sequence: self.next_sequence,
+            timestamp: ic::time(),
+            kind,
+            principal_id,
+            nft,
+        };
+
+        self.next_sequence += 1;
+        self.changes.push_back(change);
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        let window = self.retention.saturating_mul(NANOS_PER_SECOND);
+        let oldest = ic::time().saturating_sub(window);
+
+        while let Some(change) = self.changes.front() {
+            if change.timestamp >= oldest {
+                break;
+            }
+
+            self.pruned_until = change.sequence;
+            self.changes.pop_front();
+        }
+    }
+
+    pub fn set_retention(&mut self, retention: u64) {
+        self.retention = retention;
+        self.prune();
+    }
+
+    pub fn get_changes(&self, since: u64, limit: usize) -> ChangesResponse {
+        let resync = since < self.pruned_until;
+        let changes = if resync {
+            vec![]
+        } else {
+            let start = self
+                .changes
+                .partition_point(|change| change.sequence <= since);
+            self.changes.range(start..).take(limit).cloned().collect()
+        };
+
+        ChangesResponse {
+            changes,
+            latest_sequence: self.latest_sequence(),
+            resync,
+        }
+    }
+}
+
+// Returns the changes after `since`, the last sequence number the client has seen
+#[query]
+pub fn get_changes(since: u64, limit: Option<usize>) -> Result<ChangesResponse, OperationError> {
+    let limit = limit.unwrap_or(DEFAULT_CHANGES_LIMIT);
+    if limit == 0 || limit > MAX_CHANGES_LIMIT {
+        return Err(OperationError::BadParameters);
+    }
+
+    Ok(ic::get::<ChangeFeed>().get_changes(since, limit))
+}
+
+// Sets how long changes are kept, in seconds
+#[update]
+pub fn set_change_retention(retention: u64) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    ic::get_mut::<ChangeFeed>().set_retention(retention);
+    Ok(())
+}
@@ -18,6 +18,29 @@ pub enum DetailValue {
 pub const DESCRIPTION_LIMIT: usize = 1200;
 pub const NAME_LIMIT: usize = 120;
 
+// The limits of the collection entries and where they are mirrored, see `config::configure` for the
+// init arguments
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Config {
+    pub name_limit: usize,
+    pub description_limit: usize,
+    // The canister registry that collections are also listed in, `None` skips it
+    pub canister_registry: Option<Principal>,
+    // Detail keys that entries can carry besides the ones of their standard
+    pub allowed_detail_keys: Vec<String>,
+}
+
+// Stored and returned as the `standard` detail, see `NftStandard::as_str` for its text values
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NftStandard {
+    Ext,
+    Dip721,
+    Dip721v2,
+    IcPunks,
+    DepartureLabs,
+    Icrc7,
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
 pub struct AddNftInput {
     pub name: String,
@@ -41,6 +64,86 @@ pub struct NftCanister {
     pub details: Vec<(String, DetailValue)>,
 }
 
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Updated,
+    Removed,
+}
+
+// An entry of the change feed, removals are tombstones without the collection
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Change {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub kind: ChangeKind,
+    pub principal_id: Principal,
+    pub nft: Option<NftCanister>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChangesResponse {
+    pub changes: Vec<Change>,
+    pub latest_sequence: u64,
+    // Set when changes after the requested sequence were pruned, the client has to download
+    // everything with `get_all` and continue from `latest_sequence`
+    pub resync: bool,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListingState {
+    Pending,
+    Approved,
+    Rejected,
+    Deprecated,
+}
+
+// A collection submitted by the community, listed once an admin approves it
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Submission {
+    pub id: u64,
+    pub submitter: Principal,
+    pub submitted_at: u64,
+    pub input: AddNftInput,
+    pub state: ListingState,
+    pub notes: Option<String>,
+    pub reviewed_by: Option<Principal>,
+    pub reviewed_at: Option<u64>,
+}
+
+// A change that still has to be applied to the canister registry
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum SyncAction {
+    Add(AddNftInput),
+    Remove,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingOperation {
+    pub principal_id: Principal,
+    pub action: SyncAction,
+    pub trusted_source: Option<Principal>,
+    pub created_at: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+// The fields of a canister registry entry that are compared with the NFT entries
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct CanisterRegistryEntry {
+    pub name: String,
+    pub description: String,
+    pub thumbnail: String,
+    pub frontend: Option<String>,
+    pub principal_id: Principal,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReconcileReport {
+    pub repaired: Vec<Principal>,
+    pub pending: Vec<PendingOperation>,
+}
+
 #[derive(CandidType, Debug, PartialEq, Deserialize, Clone)]
 pub enum OperationError {
     NotAuthorized,
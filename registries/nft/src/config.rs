@@ -0,0 +1,72 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+use crate::standards::STANDARD_KEY;
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            name_limit: NAME_LIMIT,
+            description_limit: DESCRIPTION_LIMIT,
+            canister_registry: Some(CANISTER_REGISTRY_ID.try_into().unwrap()),
+            allowed_detail_keys: vec![],
+        }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), OperationError> {
+        let mut keys = self.allowed_detail_keys.clone();
+        keys.sort();
+        keys.dedup();
+
+        if self.name_limit == 0
+            || self.description_limit == 0
+            || keys.len() != self.allowed_detail_keys.len()
+            || keys.iter().any(|key| key.is_empty() || key == STANDARD_KEY)
+        {
+            return Err(OperationError::BadParameters);
+        }
+
+        Ok(())
+    }
+}
+
+// Stores the configuration given at init. Without one, the canister registry can still be given on
+// its own, where `aaaaa-aa` skips the canister registry
+pub fn configure(canister_registry: Option<Principal>, config: Option<Config>) {
+    let config = match (config, canister_registry) {
+        (Some(config), _) => config,
+        (None, Some(canister_registry)) => Config {
+            canister_registry: Some(canister_registry)
+                .filter(|canister_registry| *canister_registry != Principal::management_canister()),
+            ..Config::default()
+        },
+        (None, None) => return,
+    };
+
+    if let Err(err) = config.validate() {
+        ic::trap(&format!("Invalid configuration: {:?}", err));
+    }
+
+    ic::store(config);
+}
+
+#[query]
+pub fn get_config() -> &'static Config {
+    ic::get::<Config>()
+}
+
+#[update]
+pub fn set_config(config: Config) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    config.validate()?;
+    ic::store(config);
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use ic_kit::candid::{Nat, Principal};
+use ic_kit::*;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::common_types::{NftStandard, OperationError};
+
+pub type ProbeResponse = Pin<Box<dyn Future<Output = Result<(), String>>>>;
+
+// Checks that a collection answers a read-only call of its standard, implemented by a mock in the tests
+pub trait ConformanceProbe {
+    fn probe(&self, collection: Principal, standard: NftStandard) -> ProbeResponse;
+}
+
+// The method that is called for each standard
+pub fn probe_method(standard: NftStandard) -> &'static str {
+    match standard {
+        NftStandard::Ext => "extensions",
+        NftStandard::Dip721 => "totalSupplyDip721",
+        NftStandard::Dip721v2 => "totalSupply",
+        NftStandard::IcPunks => "total_supply",
+        NftStandard::DepartureLabs => "getTotalMinted",
+        NftStandard::Icrc7 => "icrc7_total_supply",
+    }
+}
+
+pub struct CollectionCanister;
+
+impl ConformanceProbe for CollectionCanister {
+    fn probe(&self, collection: Principal, standard: NftStandard) -> ProbeResponse {
+        Box::pin(async move {
+            let method = probe_method(standard);
+
+            // Only the type of the response is checked, its value does not matter
+            let response = match standard {
+                NftStandard::Ext => ic::call::<_, (Vec<String>,), _>(collection, method, ())
+                    .await
+                    .map(|_| ()),
+                NftStandard::Dip721 => ic::call::<_, (u64,), _>(collection, method, ())
+                    .await
+                    .map(|_| ()),
+                _ => ic::call::<_, (Nat,), _>(collection, method, ())
+                    .await
+                    .map(|_| ()),
+            };
+
+            response.map_err(|(_code, msg)| msg)
+        })
+    }
+}
+
+pub async fn check_conformance<P: ConformanceProbe>(
+    probe: &P,
+    collection: Principal,
+    standard: NftStandard,
+) -> Result<(), OperationError> {
+    probe.probe(collection, standard).await.map_err(|err| {
+        OperationError::Unknown(format!(
+            "The canister does not implement {}: {}",
+            standard.as_str(),
+            err
+        ))
+    })
+}
@@ -1,5 +1,11 @@
+mod changes;
 mod common_types;
+mod config;
+mod conformance;
 mod management;
 mod nft;
+mod reconciliation;
+mod standards;
+mod submissions;
 mod tests;
 mod upgrade;
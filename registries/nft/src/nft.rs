@@ -4,24 +4,22 @@ use ic_kit::*;
 use std::{collections::HashMap, str::FromStr};
 use validator::validate_url;
 
+use crate::changes::ChangeFeed;
 use crate::common_types::*;
+use crate::config::configure;
+use crate::conformance::{check_conformance, CollectionCanister, ConformanceProbe};
 use crate::management::*;
+use crate::reconciliation::{registry_input, schedule, sync};
+use crate::standards::{normalize_standard, validate_details};
 
 #[init]
-pub fn init(canister_registry: Option<Principal>) {
+pub fn init(canister_registry: Option<Principal>, config: Option<Config>) {
     ic::store(Admins(vec![ic::caller()]));
-    if let Some(canister_registry) = canister_registry {
-        ic::store(Registry(HashMap::new(), canister_registry));
-    }
+    configure(canister_registry, config);
 }
 
-// (registry map, canister registry id)
-pub struct Registry(HashMap<Principal, NftCanister>, Principal);
-impl Default for Registry {
-    fn default() -> Self {
-        Registry(HashMap::new(), CANISTER_REGISTRY_ID.try_into().unwrap())
-    }
-}
+#[derive(Default)]
+pub struct Registry(HashMap<Principal, NftCanister>);
 
 impl Registry {
     pub fn archive(&mut self) -> Vec<(Principal, NftCanister)> {
@@ -31,7 +29,13 @@ impl Registry {
 
     pub fn load(&mut self, archive: Vec<(Principal, NftCanister)>) {
         assert!(self.0.is_empty());
-        self.0 = archive.into_iter().collect();
+        self.0 = archive
+            .into_iter()
+            .map(|(principal_id, mut nft)| {
+                normalize_standard(&mut nft.details);
+                (principal_id, nft)
+            })
+            .collect();
     }
 
     pub fn add(
@@ -40,6 +44,10 @@ impl Registry {
         canister_info: AddNftInput,
     ) -> Result<(), OperationError> {
         let nft = self.0.get(&canister_info.principal_id);
+        let kind = match nft {
+            Some(_) => ChangeKind::Updated,
+            None => ChangeKind::Added,
+        };
 
         // If its an update, check if the caller matches the submitter or if its an admin
         if nft.is_some() && !is_admin(caller) && nft.unwrap().submitter != *caller {
@@ -79,6 +87,9 @@ impl Registry {
             self.0.insert(canister_info.principal_id, new_nft);
         }
 
+        let nft = self.0.get(&canister_info.principal_id).cloned();
+        ic::get_mut::<ChangeFeed>().record(kind, canister_info.principal_id, nft);
+
         Ok(())
     }
 
@@ -98,6 +109,7 @@ impl Registry {
         }
 
         self.0.remove(principal_id);
+        ic::get_mut::<ChangeFeed>().record(ChangeKind::Removed, *principal_id, None);
 
         return Ok(());
     }
@@ -116,58 +128,67 @@ fn name() -> String {
     String::from("NFT Registry Canister")
 }
 
-#[update]
-pub async fn add(
-    trusted_source: Option<Principal>,
-    canister_info: AddNftInput,
-) -> Result<(), OperationError> {
-    let caller = ic::caller();
-    if !is_admin(&caller) {
-        return Err(OperationError::NotAuthorized);
-    } else if !validate_url(&canister_info.thumbnail) {
+// Checks the urls, the limits and the details of a collection, and returns its standard
+pub fn validate_input(canister_info: &mut AddNftInput) -> Result<NftStandard, OperationError> {
+    let config = ic::get::<Config>();
+
+    if !validate_url(&canister_info.thumbnail) {
         return Err(OperationError::BadParameters);
     } else if canister_info.frontend.is_some()
         && !validate_url(&canister_info.frontend.clone().unwrap())
     {
         return Err(OperationError::BadParameters);
-    } else if canister_info.details[0].0 != String::from("standard") {
-        return Err(OperationError::BadParameters);
-    } else if canister_info.details.len() != 1 {
+    } else if canister_info.name.len() > config.name_limit
+        || canister_info.description.len() > config.description_limit
+    {
         return Err(OperationError::BadParameters);
     }
 
-    let name = canister_info.name.clone();
-    if name.len() <= NAME_LIMIT && &canister_info.description.len() <= &DESCRIPTION_LIMIT {
-        // Add the collection to the canister registry
-        let mut call_arg = canister_info.clone();
-        call_arg.details = vec![("category".to_string(), DetailValue::Text("NFT".to_string()))];
-        let canister_registry = ic::get::<Registry>().1;
-
-        // set canister registry to `aaaaa-aa` to skip canister registry insertion
-        if canister_registry != Principal::management_canister() {
-            let _registry_add_response: RegistryResponse = match ic::call(
-                canister_registry,
-                "add",
-                (trusted_source.unwrap_or(ic::id()), call_arg),
-            )
-            .await
-            {
-                Ok((x,)) => x,
-                Err((_code, msg)) => {
-                    return Err(OperationError::Unknown(msg));
-                }
-            };
-        }
+    let (standard, details) =
+        validate_details(&canister_info.details, &config.allowed_detail_keys)?;
+    canister_info.details = details;
 
-        let db = ic::get_mut::<Registry>();
-        return db.add(&trusted_source.unwrap_or(caller), canister_info);
+    Ok(standard)
+}
+
+pub async fn add_with<P: ConformanceProbe>(
+    probe: &P,
+    trusted_source: Option<Principal>,
+    mut canister_info: AddNftInput,
+) -> Result<(), OperationError> {
+    let caller = ic::caller();
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
     }
 
-    Err(OperationError::BadParameters)
+    let standard = validate_input(&mut canister_info)?;
+
+    // The collection has to answer a read-only call of its standard
+    check_conformance(probe, canister_info.principal_id, standard).await?;
+
+    let principal_id = canister_info.principal_id;
+    let call_arg = registry_input(&canister_info);
+
+    let db = ic::get_mut::<Registry>();
+    db.add(&trusted_source.unwrap_or(caller), canister_info)?;
+
+    // Add the collection to the canister registry, a failed call stays pending until it is retried
+    schedule(principal_id, SyncAction::Add(call_arg), trusted_source);
+    sync(principal_id).await.ok();
+
+    Ok(())
 }
 
 #[update]
-pub fn remove(
+pub async fn add(
+    trusted_source: Option<Principal>,
+    canister_info: AddNftInput,
+) -> Result<(), OperationError> {
+    add_with(&CollectionCanister, trusted_source, canister_info).await
+}
+
+#[update]
+pub async fn remove(
     trusted_source: Option<Principal>,
     principal_id: Principal,
 ) -> Result<(), OperationError> {
@@ -177,7 +198,13 @@ pub fn remove(
     }
 
     let db = ic::get_mut::<Registry>();
-    db.remove(&trusted_source.unwrap_or(caller), &principal_id)
+    db.remove(&trusted_source.unwrap_or(caller), &principal_id)?;
+
+    // Cascade the removal to the canister registry
+    schedule(principal_id, SyncAction::Remove, None);
+    sync(principal_id).await.ok();
+
+    Ok(())
 }
 
 #[query]
@@ -0,0 +1,277 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::collections::BTreeMap;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+use crate::nft::Registry;
+
+pub const NFT_CATEGORY: &str = "NFT";
+
+// Changes waiting to be applied to the canister registry, keyed by the principal id of the collection.
+// A newer change of a collection replaces the one that is still pending.
+#[derive(Default)]
+pub struct PendingOperations(BTreeMap<Principal, PendingOperation>);
+
+impl PendingOperations {
+    pub fn archive(&mut self) -> Vec<PendingOperation> {
+        let map = std::mem::take(&mut self.0);
+        map.into_values().collect()
+    }
+
+    pub fn load(&mut self, archive: Vec<PendingOperation>) {
+        self.0 = archive
+            .into_iter()
+            .map(|operation| (operation.principal_id, operation))
+            .collect();
+    }
+
+    pub fn push(
+        &mut self,
+        principal_id: Principal,
+        action: SyncAction,
+        trusted_source: Option<Principal>,
+    ) {
+        let operation = PendingOperation {
+            principal_id,
+            action,
+            trusted_source,
+            created_at: ic::time(),
+            attempts: 0,
+            last_error: None,
+        };
+
+        self.0.insert(principal_id, operation);
+    }
+
+    pub fn get(&self, principal_id: &Principal) -> Option<&PendingOperation> {
+        self.0.get(principal_id)
+    }
+
+    pub fn contains(&self, principal_id: &Principal) -> bool {
+        self.0.contains_key(principal_id)
+    }
+
+    pub fn get_all(&self) -> Vec<&PendingOperation> {
+        self.0.values().collect()
+    }
+
+    pub fn remove(&mut self, principal_id: &Principal) -> Option<PendingOperation> {
+        self.0.remove(principal_id)
+    }
+
+    // Records the outcome of an attempt, unless the operation was replaced while the call was running
+    fn complete(&mut self, attempted: &PendingOperation, result: Result<(), String>) {
+        let operation = match self.0.get_mut(&attempted.principal_id) {
+            Some(operation) if operation == attempted => operation,
+            _ => return,
+        };
+
+        match result {
+            Ok(()) => {
+                self.0.remove(&attempted.principal_id);
+            }
+            Err(err) => {
+                operation.attempts += 1;
+                operation.last_error = Some(err);
+            }
+        }
+    }
+}
+
+// The canister registry only keeps the category of the collections
+pub fn registry_input(canister_info: &AddNftInput) -> AddNftInput {
+    let mut registry_input = canister_info.clone();
+    registry_input.details = vec![(
+        "category".to_string(),
+        DetailValue::Text(NFT_CATEGORY.to_string()),
+    )];
+    registry_input
+}
+
+pub fn schedule(principal_id: Principal, action: SyncAction, trusted_source: Option<Principal>) {
+    if ic::get::<Config>().canister_registry.is_some() {
+        ic::get_mut::<PendingOperations>().push(principal_id, action, trusted_source);
+    }
+}
+
+async fn execute(canister_registry: Principal, operation: &PendingOperation) -> Result<(), String> {
+    let response: Result<(RegistryResponse,), _> = match &operation.action {
+        SyncAction::Add(canister_info) => {
+            ic::call(
+                canister_registry,
+                "add",
+                (
+                    Some(operation.trusted_source.unwrap_or(ic::id())),
+                    canister_info.clone(),
+                ),
+            )
+            .await
+        }
+        SyncAction::Remove => {
+            ic::call(
+                canister_registry,
+                "remove",
+                (None::<Principal>, operation.principal_id),
+            )
+            .await
+        }
+    };
+
+    match response {
+        Ok((RegistryResponse::Ok(None),)) => Ok(()),
+        // The entry was only queued for review, it is not listed until an admin accepts it
+        Ok((RegistryResponse::Ok(Some(msg)),)) => Err(msg),
+        // The entry is already gone
+        Ok((RegistryResponse::Err(OperationError::NonExistentItem),))
+            if operation.action == SyncAction::Remove =>
+        {
+            Ok(())
+        }
+        Ok((RegistryResponse::Err(err),)) => Err(format!("{:?}", err)),
+        Err((_code, msg)) => Err(msg),
+    }
+}
+
+// Applies the pending operation of the collection, a failed operation stays queued for a retry
+pub async fn sync(principal_id: Principal) -> Result<(), String> {
+    let operation = match ic::get::<PendingOperations>().0.get(&principal_id) {
+        Some(operation) => operation.clone(),
+        None => return Ok(()),
+    };
+
+    let result = match ic::get::<Config>().canister_registry {
+        Some(canister_registry) => execute(canister_registry, &operation).await,
+        None => Err(String::from("No canister registry is configured")),
+    };
+    ic::get_mut::<PendingOperations>().complete(&operation, result.clone());
+    result
+}
+
+async fn sync_all() {
+    let principal_ids: Vec<Principal> = ic::get::<PendingOperations>().0.keys().cloned().collect();
+
+    for principal_id in principal_ids {
+        sync(principal_id).await.ok();
+    }
+}
+
+fn pending_operations() -> Vec<PendingOperation> {
+    ic::get::<PendingOperations>()
+        .get_all()
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+#[query]
+pub fn get_pending_operations() -> Result<Vec<PendingOperation>, OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    Ok(pending_operations())
+}
+
+// Returns the operations that failed again
+#[update]
+pub async fn retry_pending_operations() -> Result<Vec<PendingOperation>, OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    sync_all().await;
+    Ok(pending_operations())
+}
+
+// Compares the NFT entries with the NFT category of the canister registry and repairs the entries
+// that are missing, outdated or orphaned
+#[update]
+pub async fn reconcile() -> Result<ReconcileReport, OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    let canister_registry = match ic::get::<Config>().canister_registry {
+        Some(canister_registry) => canister_registry,
+        None => {
+            return Ok(ReconcileReport {
+                repaired: vec![],
+                pending: vec![],
+            })
+        }
+    };
+
+    let response: Result<(Result<Vec<CanisterRegistryEntry>, OperationError>,), _> = ic::call(
+        canister_registry,
+        "get_by_category",
+        (NFT_CATEGORY.to_string(),),
+    )
+    .await;
+    let listed: BTreeMap<Principal, CanisterRegistryEntry> = match response {
+        Ok((Ok(entries),)) => entries
+            .into_iter()
+            .map(|entry| (entry.principal_id, entry))
+            .collect(),
+        Ok((Err(err),)) => return Err(err),
+        Err((_code, msg)) => return Err(OperationError::Unknown(msg)),
+    };
+
+    let registry = ic::get::<Registry>();
+    let queue = ic::get_mut::<PendingOperations>();
+    let mut drifted = vec![];
+
+    for nft in registry.get_all() {
+        let canister_info = AddNftInput {
+            name: nft.name.clone(),
+            description: nft.description.clone(),
+            thumbnail: nft.thumbnail.clone(),
+            frontend: nft.frontend.clone(),
+            principal_id: nft.principal_id,
+            details: nft.details.clone(),
+        };
+        let expected = CanisterRegistryEntry {
+            name: nft.name.clone(),
+            description: nft.description.clone(),
+            thumbnail: nft.thumbnail.clone(),
+            frontend: nft.frontend.clone(),
+            principal_id: nft.principal_id,
+        };
+
+        if listed.get(&nft.principal_id) == Some(&expected) {
+            // A pending addition can be dropped once the entry is listed, e.g. after a review
+            if let Some(PendingOperation {
+                action: SyncAction::Add(_),
+                ..
+            }) = queue.get(&nft.principal_id)
+            {
+                queue.remove(&nft.principal_id);
+            }
+        } else if !queue.contains(&nft.principal_id) {
+            queue.push(
+                nft.principal_id,
+                SyncAction::Add(registry_input(&canister_info)),
+                None,
+            );
+            drifted.push(nft.principal_id);
+        }
+    }
+
+    for principal_id in listed.keys() {
+        if registry.get(principal_id).is_none() && !queue.contains(principal_id) {
+            queue.push(*principal_id, SyncAction::Remove, None);
+            drifted.push(*principal_id);
+        }
+    }
+
+    sync_all().await;
+
+    let pending = pending_operations();
+    let repaired = drifted
+        .into_iter()
+        .filter(|principal_id| !ic::get::<PendingOperations>().contains(principal_id))
+        .collect();
+
+    Ok(ReconcileReport { repaired, pending })
+}
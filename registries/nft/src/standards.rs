@@ -0,0 +1,170 @@
+use crate::common_types::*;
+
+pub const STANDARD_KEY: &str = "standard";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DetailKind {
+    Text,
+    U64,
+    Vec,
+}
+
+// A detail key that entries of a standard can provide besides `standard`
+pub struct DetailSpec {
+    pub key: &'static str,
+    pub kind: DetailKind,
+    pub required: bool,
+}
+
+const EXT_DETAILS: &[DetailSpec] = &[
+    // How token identifiers are built, e.g. "tokenid" for the encoded canister id and index
+    DetailSpec {
+        key: "token_identifier_format",
+        kind: DetailKind::Text,
+        required: true,
+    },
+];
+
+const DIP721_DETAILS: &[DetailSpec] = &[DetailSpec {
+    key: "supported_interfaces",
+    kind: DetailKind::Vec,
+    required: false,
+}];
+
+// The second version of DIP721 reports its interfaces with `supportedInterfaces`
+const DIP721V2_DETAILS: &[DetailSpec] = &[DetailSpec {
+    key: "supported_interfaces",
+    kind: DetailKind::Vec,
+    required: true,
+}];
+
+const ICRC7_DETAILS: &[DetailSpec] = &[DetailSpec {
+    key: "supply_cap",
+    kind: DetailKind::U64,
+    required: false,
+}];
+
+impl NftStandard {
+    pub const ALL: [NftStandard; 6] = [
+        NftStandard::Ext,
+        NftStandard::Dip721,
+        NftStandard::Dip721v2,
+        NftStandard::IcPunks,
+        NftStandard::DepartureLabs,
+        NftStandard::Icrc7,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NftStandard::Ext => "EXT",
+            NftStandard::Dip721 => "DIP721",
+            NftStandard::Dip721v2 => "DIP721v2",
+            NftStandard::IcPunks => "ICPunks",
+            NftStandard::DepartureLabs => "DepartureLabs",
+            NftStandard::Icrc7 => "ICRC7",
+        }
+    }
+
+    pub fn details(&self) -> &'static [DetailSpec] {
+        match self {
+            NftStandard::Ext => EXT_DETAILS,
+            NftStandard::Dip721 => DIP721_DETAILS,
+            NftStandard::Dip721v2 => DIP721V2_DETAILS,
+            NftStandard::Icrc7 => ICRC7_DETAILS,
+            NftStandard::IcPunks | NftStandard::DepartureLabs => &[],
+        }
+    }
+
+    // Accepts the free text values of the existing entries, ignoring case, spaces, dashes and
+    // underscores, e.g. "Departure Labs", "dip721v2" or "ICRC-7"
+    pub fn parse(text: &str) -> Option<NftStandard> {
+        let normalize = |text: &str| -> String {
+            text.chars()
+                .filter(|c| !matches!(c, ' ' | '-' | '_'))
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let text = normalize(text);
+
+        Self::ALL
+            .into_iter()
+            .find(|standard| normalize(standard.as_str()) == text)
+    }
+}
+
+impl TryFrom<&DetailValue> for NftStandard {
+    type Error = OperationError;
+
+    fn try_from(value: &DetailValue) -> Result<Self, Self::Error> {
+        match value {
+            DetailValue::Text(text) => {
+                NftStandard::parse(text).ok_or(OperationError::BadParameters)
+            }
+            _ => Err(OperationError::BadParameters),
+        }
+    }
+}
+
+impl From<NftStandard> for DetailValue {
+    fn from(standard: NftStandard) -> Self {
+        DetailValue::Text(standard.as_str().to_string())
+    }
+}
+
+fn has_kind(value: &DetailValue, kind: DetailKind) -> bool {
+    matches!(
+        (value, kind),
+        (DetailValue::Text(_), DetailKind::Text)
+            | (DetailValue::U64(_), DetailKind::U64)
+            | (DetailValue::Vec(_), DetailKind::Vec)
+    )
+}
+
+// Checks the details against the keys of their standard and the other allowed keys, and returns the
+// standard with the details, where the normalised standard is the first detail
+pub fn validate_details(
+    details: &[(String, DetailValue)],
+    allowed_keys: &[String],
+) -> Result<(NftStandard, Vec<(String, DetailValue)>), OperationError> {
+    let mut standards = details.iter().filter(|(key, _)| key == STANDARD_KEY);
+    let standard = match (standards.next(), standards.next()) {
+        (Some((_, value)), None) => NftStandard::try_from(value)?,
+        _ => return Err(OperationError::BadParameters),
+    };
+
+    let mut normalized = vec![(STANDARD_KEY.to_string(), DetailValue::from(standard))];
+
+    for (key, value) in details.iter().filter(|(key, _)| key != STANDARD_KEY) {
+        let valid = match standard.details().iter().find(|spec| spec.key == key) {
+            Some(spec) => has_kind(value, spec.kind),
+            None => allowed_keys.contains(key),
+        };
+
+        if !valid || normalized.iter().any(|(other, _)| other == key) {
+            return Err(OperationError::BadParameters);
+        }
+
+        normalized.push((key.clone(), value.clone()));
+    }
+
+    let missing = standard
+        .details()
+        .iter()
+        .any(|spec| spec.required && !normalized.iter().any(|(key, _)| key == spec.key));
+    if missing {
+        return Err(OperationError::BadParameters);
+    }
+
+    Ok((standard, normalized))
+}
+
+// Rewrites the standard of an existing entry to its normalised form, unknown values are kept
+pub fn normalize_standard(details: &mut [(String, DetailValue)]) {
+    for (key, value) in details.iter_mut() {
+        if key == STANDARD_KEY {
+            if let Ok(standard) = NftStandard::try_from(&*value) {
+                *value = DetailValue::from(standard);
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::collections::BTreeMap;
+
+use crate::common_types::*;
+use crate::conformance::{CollectionCanister, ConformanceProbe};
+use crate::management::is_admin;
+use crate::nft::{add_with, remove, validate_input, Registry};
+
+// Submissions waiting for a review, so that the queue cannot grow without bound
+pub const MAX_PENDING_PER_SUBMITTER: usize = 5;
+pub const MAX_PENDING_SUBMISSIONS: usize = 500;
+
+// (submissions by id, next id)
+#[derive(Default)]
+pub struct Submissions(BTreeMap<u64, Submission>, u64);
+
+impl Submissions {
+    pub fn archive(&mut self) -> Vec<Submission> {
+        let map = std::mem::take(&mut self.0);
+        map.into_values().collect()
+    }
+
+    pub fn load(&mut self, archive: Vec<Submission>) {
+        self.0 = archive
+            .into_iter()
+            .map(|submission| (submission.id, submission))
+            .collect();
+        self.1 = self.0.keys().next_back().map_or(0, |id| id + 1);
+    }
+
+    pub fn push(&mut self, submitter: &Principal, input: AddNftInput) -> u64 {
+        let id = self.1;
+        self.1 += 1;
+
+        self.0.insert(
+            id,
+            Submission {
+                id,
+                submitter: *submitter,
+                submitted_at: ic::time(),
+                input,
+                state: ListingState::Pending,
+                notes: None,
+                reviewed_by: None,
+                reviewed_at: None,
+            },
+        );
+
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Submission> {
+        self.0.get(&id)
+    }
+
+    pub fn get_all(&self) -> impl Iterator<Item = &Submission> {
+        self.0.values()
+    }
+
+    pub fn is_pending(&self, principal_id: &Principal) -> bool {
+        self.0.values().any(|submission| {
+            submission.state == ListingState::Pending
+                && submission.input.principal_id == *principal_id
+        })
+    }
+
+    // (pending submissions of the submitter, pending submissions)
+    pub fn pending_count(&self, submitter: &Principal) -> (usize, usize) {
+        self.0
+            .values()
+            .filter(|submission| submission.state == ListingState::Pending)
+            .fold((0, 0), |(own, all), submission| {
+                (own + (submission.submitter == *submitter) as usize, all + 1)
+            })
+    }
+
+    // Moves a submission from one state to the next one and records the review
+    pub fn review(
+        &mut self,
+        id: u64,
+        reviewer: &Principal,
+        from: ListingState,
+        to: ListingState,
+        notes: Option<String>,
+    ) -> Result<(), OperationError> {
+        let submission = self.0.get_mut(&id).ok_or(OperationError::NonExistentItem)?;
+        if submission.state != from {
+            return Err(OperationError::BadParameters);
+        }
+
+        submission.state = to;
+        submission.notes = notes;
+        submission.reviewed_by = Some(*reviewer);
+        submission.reviewed_at = Some(ic::time());
+
+        Ok(())
+    }
+
+    // Returns the submission if it is in the expected state
+    fn get_in_state(&self, id: u64, state: ListingState) -> Result<&Submission, OperationError> {
+        let submission = self.get(id).ok_or(OperationError::NonExistentItem)?;
+        if submission.state != state {
+            return Err(OperationError::BadParameters);
+        }
+
+        Ok(submission)
+    }
+}
+
+// Anyone can submit a collection that is not listed yet, it is listed once an admin approves it
+#[update]
+pub fn submit(mut canister_info: AddNftInput) -> Result<u64, OperationError> {
+    let caller = ic::caller();
+    if caller == Principal::anonymous() {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    validate_input(&mut canister_info)?;
+
+    let submissions = ic::get_mut::<Submissions>();
+    if ic::get::<Registry>()
+        .get(&canister_info.principal_id)
+        .is_some()
+        || submissions.is_pending(&canister_info.principal_id)
+    {
+        return Err(OperationError::BadParameters);
+    }
+
+    let (own, all) = submissions.pending_count(&caller);
+    if own >= MAX_PENDING_PER_SUBMITTER || all >= MAX_PENDING_SUBMISSIONS {
+        return Err(OperationError::BadParameters);
+    }
+
+    Ok(submissions.push(&caller, canister_info))
+}
+
+pub async fn approve_submission_with<P: ConformanceProbe>(
+    probe: &P,
+    id: u64,
+    notes: Option<String>,
+) -> Result<(), OperationError> {
+    let caller = ic::caller();
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    let submission = ic::get::<Submissions>()
+        .get_in_state(id, ListingState::Pending)?
+        .clone();
+
+    // The submitter is recorded as the submitter of the listing
+    add_with(probe, Some(submission.submitter), submission.input).await?;
+
+    ic::get_mut::<Submissions>().review(
+        id,
+        &caller,
+        ListingState::Pending,
+        ListingState::Approved,
+        notes,
+    )
+}
+
+#[update]
+pub async fn approve_submission(id: u64, notes: Option<String>) -> Result<(), OperationError> {
+    approve_submission_with(&CollectionCanister, id, notes).await
+}
+
+#[update]
+pub fn reject_submission(id: u64, notes: String) -> Result<(), OperationError> {
+    let caller = ic::caller();
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    ic::get_mut::<Submissions>().review(
+        id,
+        &caller,
+        ListingState::Pending,
+        ListingState::Rejected,
+        Some(notes),
+    )
+}
+
+// Unlists an approved collection, e.g. when it was abandoned or migrated to a new canister
+#[update]
+pub async fn deprecate_submission(id: u64, notes: String) -> Result<(), OperationError> {
+    let caller = ic::caller();
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    let principal_id = ic::get::<Submissions>()
+        .get_in_state(id, ListingState::Approved)?
+        .input
+        .principal_id;
+
+    match remove(None, principal_id).await {
+        Ok(()) | Err(OperationError::NonExistentItem) => (),
+        Err(err) => return Err(err),
+    }
+
+    ic::get_mut::<Submissions>().review(
+        id,
+        &caller,
+        ListingState::Approved,
+        ListingState::Deprecated,
+        Some(notes),
+    )
+}
+
+// Submitters can follow their own submissions, admins can see every submission
+#[query]
+pub fn get_submission(id: u64) -> Result<&'static Submission, OperationError> {
+    let submission = ic::get::<Submissions>()
+        .get(id)
+        .ok_or(OperationError::NonExistentItem)?;
+
+    let caller = ic::caller();
+    if submission.submitter != caller && !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    Ok(submission)
+}
+
+#[query]
+pub fn get_my_submissions() -> Vec<&'static Submission> {
+    let caller = ic::caller();
+
+    ic::get::<Submissions>()
+        .get_all()
+        .filter(|submission| submission.submitter == caller)
+        .collect()
+}
+
+#[query]
+pub fn get_submissions(
+    state: Option<ListingState>,
+) -> Result<Vec<&'static Submission>, OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    Ok(ic::get::<Submissions>()
+        .get_all()
+        .filter(|submission| state.is_none() || state == Some(submission.state))
+        .collect())
+}
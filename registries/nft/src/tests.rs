@@ -389,3 +389,693 @@
 //         assert_eq!(get_all_result.len(), 0);
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use crate::changes::{get_changes, set_change_retention, ChangeFeed};
+    use crate::common_types::*;
+    use crate::config::{get_config, set_config};
+    use crate::conformance::{probe_method, ConformanceProbe, ProbeResponse};
+    use crate::nft::{add_with, get, init, remove};
+    use crate::reconciliation::{get_pending_operations, reconcile, retry_pending_operations};
+    use crate::standards::validate_details;
+    use crate::submissions::{
+        approve_submission_with, deprecate_submission, get_my_submissions, get_submission,
+        get_submissions, reject_submission, submit, MAX_PENDING_PER_SUBMITTER,
+    };
+    use ic_kit::*;
+    use std::cell::{Cell, RefCell};
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+
+    fn nft_input(details: Vec<(String, DetailValue)>) -> AddNftInput {
+        AddNftInput {
+            name: String::from("xtc"),
+            description: String::from("XTC is your cycles wallet."),
+            thumbnail: String::from("https://google.com"),
+            frontend: None,
+            principal_id: mock_principals::xtc(),
+            details,
+        }
+    }
+
+    // EXT collections have to tell how their token identifiers are built
+    fn ext_details(standard: &str) -> Vec<(String, DetailValue)> {
+        vec![
+            text("standard", standard),
+            text("token_identifier_format", "tokenid"),
+        ]
+    }
+
+    // Answers the probe of the standard the collection implements, `None` for a canister that is not
+    // a collection
+    struct MockCollection(Option<NftStandard>);
+
+    impl ConformanceProbe for MockCollection {
+        fn probe(&self, _collection: Principal, standard: NftStandard) -> ProbeResponse {
+            let result = match self.0 {
+                Some(implemented) if implemented == standard => Ok(()),
+                _ => Err(format!("no method {}", probe_method(standard))),
+            };
+            Box::pin(async move { result })
+        }
+    }
+
+    type Listed = Rc<RefCell<BTreeMap<Principal, CanisterRegistryEntry>>>;
+
+    // Answers like the canister registry, with the entries in `listed`. Calls are rejected while
+    // `offline` is set and additions are only queued for review while `reviewing` is set.
+    fn canister_registry(
+        listed: Listed,
+        offline: Rc<Cell<bool>>,
+        reviewing: Rc<Cell<bool>>,
+    ) -> RawHandler {
+        RawHandler::raw(Box::new(move |_, args, _, method| {
+            if offline.get() {
+                return Err((RejectionCode::CanisterError, String::from("offline")));
+            }
+
+            let mut listed = listed.borrow_mut();
+            let response = match method {
+                "add" => {
+                    let (_, input): (Option<Principal>, AddNftInput) =
+                        candid::decode_args(args).unwrap();
+                    if reviewing.get() {
+                        let msg = String::from("The entry has been flagged for review");
+                        return Ok(candid::encode_args((RegistryResponse::Ok(Some(msg)),)).unwrap());
+                    }
+                    let entry = CanisterRegistryEntry {
+                        name: input.name,
+                        description: input.description,
+                        thumbnail: input.thumbnail,
+                        frontend: input.frontend,
+                        principal_id: input.principal_id,
+                    };
+                    listed.insert(entry.principal_id, entry);
+                    candid::encode_args((RegistryResponse::Ok(None),))
+                }
+                "remove" => {
+                    let (_, principal_id): (Option<Principal>, Principal) =
+                        candid::decode_args(args).unwrap();
+                    let response = match listed.remove(&principal_id) {
+                        Some(_) => RegistryResponse::Ok(None),
+                        None => RegistryResponse::Err(OperationError::NonExistentItem),
+                    };
+                    candid::encode_args((response,))
+                }
+                _ => {
+                    let entries: Vec<CanisterRegistryEntry> = listed.values().cloned().collect();
+                    candid::encode_args((Ok::<_, OperationError>(entries),))
+                }
+            };
+
+            Ok(response.unwrap())
+        }))
+    }
+
+    fn text(key: &str, value: &str) -> (String, DetailValue) {
+        (String::from(key), DetailValue::Text(String::from(value)))
+    }
+
+    #[test]
+    fn test_standards_are_parsed_from_legacy_text() {
+        assert_eq!(NftStandard::parse("EXT"), Some(NftStandard::Ext));
+        assert_eq!(NftStandard::parse("dip721v2"), Some(NftStandard::Dip721v2));
+        assert_eq!(
+            NftStandard::parse("Departure Labs"),
+            Some(NftStandard::DepartureLabs)
+        );
+        assert_eq!(NftStandard::parse("ICRC-7"), Some(NftStandard::Icrc7));
+        assert_eq!(NftStandard::parse("Dank"), None);
+
+        assert_eq!(
+            NftStandard::try_from(&DetailValue::Text(String::from("icpunks"))),
+            Ok(NftStandard::IcPunks)
+        );
+        assert_eq!(
+            NftStandard::try_from(&DetailValue::U64(721)),
+            Err(OperationError::BadParameters)
+        );
+    }
+
+    #[test]
+    fn test_details_are_validated_per_standard() {
+        // The standard is normalised and moved first
+        assert_eq!(
+            validate_details(
+                &[
+                    text("token_identifier_format", "tokenid"),
+                    text("standard", "ext"),
+                ],
+                &[]
+            ),
+            Ok((
+                NftStandard::Ext,
+                vec![
+                    text("standard", "EXT"),
+                    text("token_identifier_format", "tokenid"),
+                ]
+            ))
+        );
+
+        assert_eq!(
+            validate_details(&[], &[]),
+            Err(OperationError::BadParameters)
+        );
+        assert_eq!(
+            validate_details(&[text("standard", "EXT"), text("standard", "DIP721")], &[]),
+            Err(OperationError::BadParameters)
+        );
+        // Keys of another standard and values of the wrong kind are rejected
+        assert_eq!(
+            validate_details(
+                &[
+                    text("standard", "DIP721"),
+                    text("token_identifier_format", "tokenid"),
+                ],
+                &[]
+            ),
+            Err(OperationError::BadParameters)
+        );
+        assert_eq!(
+            validate_details(
+                &[text("standard", "ICRC7"), text("supply_cap", "10000")],
+                &[]
+            ),
+            Err(OperationError::BadParameters)
+        );
+        assert!(validate_details(
+            &[
+                text("standard", "ICRC7"),
+                (String::from("supply_cap"), DetailValue::U64(10000)),
+            ],
+            &[]
+        )
+        .is_ok());
+
+        // The required keys of a standard have to be given
+        assert_eq!(
+            validate_details(&[text("standard", "EXT")], &[]),
+            Err(OperationError::BadParameters)
+        );
+        assert_eq!(
+            validate_details(&[text("standard", "DIP721v2")], &[]),
+            Err(OperationError::BadParameters)
+        );
+        assert!(validate_details(
+            &[
+                text("standard", "DIP721v2"),
+                (
+                    String::from("supported_interfaces"),
+                    DetailValue::Vec(vec![DetailValue::Text(String::from("Mint"))])
+                ),
+            ],
+            &[]
+        )
+        .is_ok());
+
+        // The keys allowed by the config are accepted for every standard
+        let allowed_keys = [String::from("twitter")];
+        assert!(validate_details(
+            &[text("standard", "ICPunks"), text("twitter", "@icpunks")],
+            &allowed_keys
+        )
+        .is_ok());
+        assert_eq!(
+            validate_details(
+                &[
+                    text("standard", "ICPunks"),
+                    text("twitter", "@icpunks"),
+                    text("twitter", "@punks"),
+                ],
+                &allowed_keys
+            ),
+            Err(OperationError::BadParameters)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_add_normalises_the_standard() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        // The management canister skips the canister registry call
+        init(Some(Principal::management_canister()), None);
+
+        assert_eq!(
+            add_with(
+                &MockCollection(Some(NftStandard::Dip721)),
+                None,
+                nft_input(vec![])
+            )
+            .await,
+            Err(OperationError::BadParameters)
+        );
+        assert_eq!(
+            add_with(
+                &MockCollection(Some(NftStandard::Dip721)),
+                None,
+                nft_input(vec![text("standard", "Dank")])
+            )
+            .await,
+            Err(OperationError::BadParameters)
+        );
+        // An EXT entry without its token identifier format
+        assert_eq!(
+            add_with(
+                &MockCollection(Some(NftStandard::Ext)),
+                None,
+                nft_input(vec![text("standard", "EXT")])
+            )
+            .await,
+            Err(OperationError::BadParameters)
+        );
+
+        assert!(add_with(
+            &MockCollection(Some(NftStandard::Dip721)),
+            None,
+            nft_input(vec![text("standard", "dip-721")])
+        )
+        .await
+        .is_ok());
+        assert_eq!(
+            get(mock_principals::xtc()).unwrap().details,
+            vec![text("standard", "DIP721")]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_collections_have_to_implement_their_standard() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(Principal::management_canister()), None);
+
+        let dip721 = nft_input(vec![text("standard", "DIP721")]);
+        match add_with(
+            &MockCollection(Some(NftStandard::Ext)),
+            None,
+            dip721.clone(),
+        )
+        .await
+        {
+            Err(OperationError::Unknown(msg)) => assert!(msg.contains("totalSupplyDip721")),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(add_with(&MockCollection(None), None, dip721.clone())
+            .await
+            .is_err());
+        assert!(get(mock_principals::xtc()).is_none());
+
+        assert!(
+            add_with(&MockCollection(Some(NftStandard::Dip721)), None, dip721)
+                .await
+                .is_ok()
+        );
+        assert!(get(mock_principals::xtc()).is_some());
+    }
+
+    #[async_std::test]
+    async fn test_submissions_are_reviewed_by_admins() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(Principal::management_canister()), None);
+        let collection = MockCollection(Some(NftStandard::Ext));
+
+        ctx.update_caller(mock_principals::bob());
+        let xtc = nft_input(ext_details("ext"));
+        let xtc_id = submit(xtc.clone()).unwrap();
+        // A collection can only have one pending submission
+        assert_eq!(submit(xtc), Err(OperationError::BadParameters));
+
+        let mut punks = nft_input(vec![text("standard", "ICPunks")]);
+        punks.principal_id = mock_principals::john();
+        let punks_id = submit(punks).unwrap();
+
+        let submissions = get_my_submissions();
+        assert_eq!(submissions.len(), 2);
+        assert_eq!(submissions[0].state, ListingState::Pending);
+        // The details are normalised on submission
+        assert_eq!(submissions[0].input.details, ext_details("EXT"));
+
+        assert_eq!(
+            approve_submission_with(&collection, xtc_id, None).await,
+            Err(OperationError::NotAuthorized)
+        );
+        assert_eq!(
+            get_submissions(None).unwrap_err(),
+            OperationError::NotAuthorized
+        );
+
+        ctx.update_caller(mock_principals::alice());
+        assert!(
+            approve_submission_with(&collection, xtc_id, Some(String::from("Looks good")))
+                .await
+                .is_ok()
+        );
+        ctx.call_state_reset();
+        assert!(reject_submission(punks_id, String::from("Not a collection")).is_ok());
+        assert_eq!(
+            reject_submission(xtc_id, String::new()),
+            Err(OperationError::BadParameters)
+        );
+        assert_eq!(
+            get_submissions(Some(ListingState::Pending)).unwrap().len(),
+            0
+        );
+
+        // The submitter of the listing is the submitter of the collection
+        assert_eq!(
+            get(mock_principals::xtc()).unwrap().submitter,
+            mock_principals::bob()
+        );
+        assert!(get(mock_principals::john()).is_none());
+
+        ctx.update_caller(mock_principals::bob());
+        let submission = get_submission(xtc_id).unwrap();
+        assert_eq!(submission.state, ListingState::Approved);
+        assert_eq!(submission.notes, Some(String::from("Looks good")));
+        assert_eq!(submission.reviewed_by, Some(mock_principals::alice()));
+        assert_eq!(
+            get_submission(punks_id).unwrap().state,
+            ListingState::Rejected
+        );
+
+        ctx.update_caller(mock_principals::john());
+        assert_eq!(
+            get_submission(xtc_id).unwrap_err(),
+            OperationError::NotAuthorized
+        );
+
+        ctx.update_caller(mock_principals::alice());
+        assert!(deprecate_submission(xtc_id, String::from("Migrated"))
+            .await
+            .is_ok());
+        ctx.call_state_reset();
+        assert!(get(mock_principals::xtc()).is_none());
+        assert_eq!(
+            get_submission(xtc_id).unwrap().state,
+            ListingState::Deprecated
+        );
+    }
+
+    #[test]
+    fn test_pending_submissions_are_capped() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(Principal::management_canister()), None);
+
+        ctx.update_caller(mock_principals::bob());
+        // A submitter can only have a few submissions waiting for a review
+        let mut ids = vec![];
+        for i in 0..MAX_PENDING_PER_SUBMITTER as u8 {
+            let mut input = nft_input(ext_details("EXT"));
+            input.principal_id = Principal::from_slice(&[i]);
+            ids.push(submit(input).unwrap());
+        }
+        let mut input = nft_input(ext_details("EXT"));
+        input.principal_id = Principal::from_slice(&[0xff]);
+        assert_eq!(submit(input.clone()), Err(OperationError::BadParameters));
+
+        // Other submitters are not affected, and a review frees a slot
+        ctx.update_caller(mock_principals::john());
+        let mut other = nft_input(ext_details("EXT"));
+        other.principal_id = Principal::from_slice(&[0xfe]);
+        assert!(submit(other).is_ok());
+
+        ctx.update_caller(mock_principals::alice());
+        assert!(reject_submission(ids[0], String::from("Duplicate")).is_ok());
+        ctx.update_caller(mock_principals::bob());
+        assert!(submit(input).is_ok());
+    }
+
+    #[async_std::test]
+    async fn test_change_feed_follows_the_registry() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(Principal::management_canister()), None);
+        let collection = MockCollection(Some(NftStandard::Ext));
+
+        let mut xtc = nft_input(ext_details("EXT"));
+        assert!(add_with(&collection, None, xtc.clone()).await.is_ok());
+        ctx.call_state_reset();
+        xtc.name = String::from("Cycles");
+        assert!(add_with(&collection, None, xtc).await.is_ok());
+        ctx.call_state_reset();
+        assert!(remove(None, mock_principals::xtc()).await.is_ok());
+        ctx.call_state_reset();
+
+        let response = get_changes(0, None).unwrap();
+        assert!(!response.resync);
+        assert_eq!(response.latest_sequence, 3);
+        let kinds: Vec<(u64, ChangeKind)> = response
+            .changes
+            .iter()
+            .map(|change| (change.sequence, change.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (1, ChangeKind::Added),
+                (2, ChangeKind::Updated),
+                (3, ChangeKind::Removed)
+            ]
+        );
+        assert_eq!(
+            response.changes[1].nft.as_ref().unwrap().name,
+            String::from("Cycles")
+        );
+        // Removals are tombstones
+        assert_eq!(response.changes[2].principal_id, mock_principals::xtc());
+        assert!(response.changes[2].nft.is_none());
+
+        let response = get_changes(1, Some(1)).unwrap();
+        assert_eq!(response.changes.len(), 1);
+        assert_eq!(response.changes[0].sequence, 2);
+        assert!(get_changes(3, None).unwrap().changes.is_empty());
+        assert_eq!(get_changes(0, Some(0)), Err(OperationError::BadParameters));
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(set_change_retention(0), Err(OperationError::NotAuthorized));
+
+        // Clients that missed pruned changes have to download everything again
+        ctx.update_caller(mock_principals::alice());
+        assert!(set_change_retention(0).is_ok());
+        let response = get_changes(0, None).unwrap();
+        assert!(response.resync);
+        assert!(response.changes.is_empty());
+        assert_eq!(response.latest_sequence, 3);
+        assert!(!get_changes(3, None).unwrap().resync);
+    }
+
+    #[test]
+    fn test_change_feed_started_after_existing_entries_asks_for_a_resync() {
+        MockContext::new().inject();
+
+        let mut feed = ChangeFeed::default();
+        feed.start_after_snapshot();
+        let response = feed.get_changes(0, 10);
+        assert!(response.resync);
+
+        feed.record(ChangeKind::Removed, mock_principals::xtc(), None);
+        let mut restored = ChangeFeed::default();
+        restored.load(feed.archive());
+        let response = restored.get_changes(response.latest_sequence, 10);
+        assert!(!response.resync);
+        assert_eq!(response.changes.len(), 1);
+        assert_eq!(response.changes[0].sequence, 2);
+    }
+
+    #[async_std::test]
+    async fn test_canister_registry_operations_are_retried_and_reconciled() {
+        let listed = Listed::default();
+        let offline = Rc::new(Cell::new(true));
+
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .with_handler(canister_registry(
+                listed.clone(),
+                offline.clone(),
+                Rc::new(Cell::new(false)),
+            ))
+            .inject();
+
+        init(Some(mock_principals::john()), None);
+
+        // The entry is added locally and the failed call stays pending
+        let xtc = nft_input(ext_details("EXT"));
+        assert!(
+            add_with(&MockCollection(Some(NftStandard::Ext)), None, xtc.clone())
+                .await
+                .is_ok()
+        );
+        // The context stays in reply callback mode after an inter-canister call
+        ctx.call_state_reset();
+        assert!(get(mock_principals::xtc()).is_some());
+
+        let pending = get_pending_operations().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+        assert_eq!(pending[0].last_error, Some(String::from("offline")));
+        assert!(listed.borrow().is_empty());
+
+        offline.set(false);
+        assert_eq!(retry_pending_operations().await, Ok(vec![]));
+        ctx.call_state_reset();
+        assert_eq!(listed.borrow()[&mock_principals::xtc()].name, xtc.name);
+
+        // Removals are cascaded
+        assert!(remove(None, mock_principals::xtc()).await.is_ok());
+        ctx.call_state_reset();
+        assert!(listed.borrow().is_empty());
+
+        // An outdated entry is updated and an orphaned one is removed
+        assert!(
+            add_with(&MockCollection(Some(NftStandard::Ext)), None, xtc.clone())
+                .await
+                .is_ok()
+        );
+        ctx.call_state_reset();
+        listed
+            .borrow_mut()
+            .get_mut(&mock_principals::xtc())
+            .unwrap()
+            .name = String::from("Phishing");
+        listed.borrow_mut().insert(
+            mock_principals::bob(),
+            CanisterRegistryEntry {
+                name: String::from("Orphan"),
+                description: String::new(),
+                thumbnail: String::from("https://google.com"),
+                frontend: None,
+                principal_id: mock_principals::bob(),
+            },
+        );
+
+        let report = reconcile().await.unwrap();
+        ctx.call_state_reset();
+        assert!(report.pending.is_empty());
+        assert_eq!(report.repaired.len(), 2);
+        assert!(report.repaired.contains(&mock_principals::xtc()));
+        assert!(report.repaired.contains(&mock_principals::bob()));
+        assert_eq!(listed.borrow().len(), 1);
+        assert_eq!(listed.borrow()[&mock_principals::xtc()].name, xtc.name);
+
+        // Nothing to repair once both registries agree
+        assert!(reconcile().await.unwrap().repaired.is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_operations_queued_for_review_stay_pending() {
+        let listed = Listed::default();
+        let reviewing = Rc::new(Cell::new(false));
+
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .with_handler(canister_registry(
+                listed.clone(),
+                Rc::new(Cell::new(false)),
+                reviewing.clone(),
+            ))
+            .inject();
+
+        init(Some(mock_principals::john()), None);
+
+        let xtc = nft_input(ext_details("EXT"));
+        assert!(
+            add_with(&MockCollection(Some(NftStandard::Ext)), None, xtc.clone())
+                .await
+                .is_ok()
+        );
+        ctx.call_state_reset();
+
+        // An update that is only queued for review stays pending
+        reviewing.set(true);
+        let renamed = AddNftInput {
+            name: String::from("XTC Renamed"),
+            ..xtc.clone()
+        };
+        assert!(add_with(
+            &MockCollection(Some(NftStandard::Ext)),
+            None,
+            renamed.clone()
+        )
+        .await
+        .is_ok());
+        ctx.call_state_reset();
+        assert_eq!(listed.borrow()[&mock_principals::xtc()].name, xtc.name);
+
+        let pending = get_pending_operations().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            pending[0].last_error,
+            Some(String::from("The entry has been flagged for review"))
+        );
+
+        let report = reconcile().await.unwrap();
+        ctx.call_state_reset();
+        assert!(report.repaired.is_empty());
+        assert_eq!(report.pending.len(), 1);
+
+        // Once the review accepts it, the pending addition is dropped
+        listed
+            .borrow_mut()
+            .get_mut(&mock_principals::xtc())
+            .unwrap()
+            .name = renamed.name.clone();
+        let report = reconcile().await.unwrap();
+        ctx.call_state_reset();
+        assert!(report.repaired.is_empty());
+        assert!(report.pending.is_empty());
+        assert!(get_pending_operations().unwrap().is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_config_is_set_at_init_and_by_admins() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        // The canister registry given on its own keeps the default limits
+        init(Some(Principal::management_canister()), None);
+        assert_eq!(get_config().canister_registry, None);
+        assert_eq!(get_config().name_limit, NAME_LIMIT);
+
+        let mut canister_info = nft_input(ext_details("EXT"));
+        canister_info.name = "x".repeat(NAME_LIMIT + 1);
+
+        let probe = MockCollection(Some(NftStandard::Ext));
+        assert_eq!(
+            add_with(&probe, None, canister_info.clone()).await,
+            Err(OperationError::BadParameters)
+        );
+
+        let config = Config {
+            name_limit: NAME_LIMIT * 2,
+            canister_registry: None,
+            ..Config::default()
+        };
+        assert!(set_config(config.clone()).is_ok());
+        assert!(add_with(&probe, None, canister_info).await.is_ok());
+        ctx.call_state_reset();
+
+        assert_eq!(
+            set_config(Config {
+                name_limit: 0,
+                ..config.clone()
+            }),
+            Err(OperationError::BadParameters)
+        );
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(set_config(config), Err(OperationError::NotAuthorized));
+    }
+}
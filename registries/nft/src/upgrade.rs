@@ -1,6 +1,9 @@
-use crate::common_types::NftCanister;
+use crate::changes::{ChangeFeed, ChangeFeedArchive};
+use crate::common_types::{Config, NftCanister, PendingOperation, Submission};
 use crate::management::Admins;
 use crate::nft::Registry;
+use crate::reconciliation::PendingOperations;
+use crate::submissions::Submissions;
 
 use ic_kit::candid::{CandidType, Deserialize, Principal};
 use ic_kit::ic::*;
@@ -11,14 +14,29 @@ use ic_kit::*;
 struct StableStorage {
     db: Vec<(Principal, NftCanister)>,
     admins: Vec<Principal>,
+    pending_operations: Option<Vec<PendingOperation>>,
+    submissions: Option<Vec<Submission>>,
+    changes: Option<ChangeFeedArchive>,
+    config: Option<Config>,
 }
 
 #[pre_upgrade]
 pub fn pre_upgrade() {
     let db = ic::get_mut::<Registry>().archive();
     let admins = ic::get_mut::<Admins>().0.clone();
+    let pending_operations = Some(ic::get_mut::<PendingOperations>().archive());
+    let submissions = Some(ic::get_mut::<Submissions>().archive());
+    let changes = Some(ic::get_mut::<ChangeFeed>().archive());
+    let config = Some(ic::get::<Config>().clone());
 
-    let stable = StableStorage { db, admins };
+    let stable = StableStorage {
+        db,
+        admins,
+        pending_operations,
+        submissions,
+        changes,
+        config,
+    };
 
     match ic::stable_store((stable,)) {
         Ok(_) => (),
@@ -36,5 +54,13 @@ pub fn post_upgrade() {
     if let Ok((stable,)) = ic::stable_restore::<(StableStorage,)>() {
         ic::get_mut::<Registry>().load(stable.db);
         ic::store(Admins(stable.admins));
+        ic::get_mut::<PendingOperations>().load(stable.pending_operations.unwrap_or_default());
+        ic::get_mut::<Submissions>().load(stable.submissions.unwrap_or_default());
+        ic::store(stable.config.unwrap_or_default());
+
+        match stable.changes {
+            Some(changes) => ic::get_mut::<ChangeFeed>().load(changes),
+            None => ic::get_mut::<ChangeFeed>().start_after_snapshot(),
+        }
     }
 }
@@ -1,4 +1,7 @@
-use ic_kit::{candid::CandidType, Principal};
+use ic_kit::{
+    candid::{CandidType, Nat},
+    Principal,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(CandidType, Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -23,6 +26,8 @@ pub struct AddTokenInput {
     pub frontend: Option<String>,
     pub principal_id: Principal,
     pub details: Vec<(String, DetailValue)>,
+    // Keeps the relationships of the token when it is not given
+    pub relationships: Option<Vec<TokenRelationship>>,
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
@@ -36,9 +41,129 @@ pub struct Token {
     pub last_updated_by: Principal,
     pub last_updated_at: u64,
     pub details: Vec<(String, DetailValue)>,
+    pub ledger: Option<IcrcLedger>,
+    // The last change of the verified status, the status itself is also the `verified` detail
+    pub verification: Option<VerificationRecord>,
+    pub relationships: Option<Vec<TokenRelationship>>,
 }
 
-#[derive(CandidType, Debug, Deserialize)]
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum WrappedAsset {
+    Token(Principal),
+    Cycles,
+}
+
+// How a token relates to other assets, e.g. WICP wraps ICP
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum TokenRelationship {
+    Wraps(WrappedAsset),
+    // A token bridged from another chain, e.g. ckETH
+    BridgedFrom {
+        chain: String,
+        contract_address: String,
+    },
+    // The liquidity pool token of a pair
+    LpOf {
+        token0: Principal,
+        token1: Principal,
+    },
+}
+
+// A logo uploaded to the registry, served through `http_request`
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Logo {
+    pub content_type: String,
+    // Hex encoded, it is part of the URL of the logo
+    pub sha256: String,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+pub type HeaderField = (String, String);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<HeaderField>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<HeaderField>,
+    #[serde(with = "serde_bytes")]
+    pub body: Vec<u8>,
+}
+
+// The semantic version of the token list, with the time of its last change in nanoseconds
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct TokenListVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub timestamp: u64,
+}
+
+// A change of the verified status of a token, made by an admin
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct VerificationRecord {
+    pub verified: bool,
+    pub reason: String,
+    pub changed_by: Principal,
+    pub changed_at: u64,
+}
+
+// A standard that an ICRC ledger implements, as listed by `icrc1_supported_standards`
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct SupportedStandard {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<Vec<u8>>,
+}
+
+// What the ledger of an ICRC-1 or ICRC-2 token reports about itself, with its companion canisters
+#[derive(CandidType, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct IcrcLedger {
+    pub supported_standards: Vec<SupportedStandard>,
+    pub minting_account: Option<Account>,
+    pub logo: Option<String>,
+    pub index_canister: Option<Principal>,
+    pub archive_canisters: Vec<Principal>,
+}
+
+// The typed form of the required details, which are still stored and sent as `details`
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct TokenDetails {
+    pub symbol: String,
+    pub standard: String,
+    pub total_supply: Nat,
+    pub verified: bool,
+    pub decimals: u8,
+    pub fee: Nat,
+    // Only ICRC tokens have an index canister
+    pub index_canister: Option<Principal>,
+}
+
+// The limits of the token entries and the canister registry they are also listed in
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct Config {
+    pub name_limit: usize,
+    pub description_limit: usize,
+    // The canister registry that tokens are also listed in, `None` skips it
+    pub canister_registry: Option<Principal>,
+    // Detail keys that tokens can carry after the required ones
+    pub allowed_detail_keys: Vec<String>,
+}
+
+#[derive(CandidType, Debug, PartialEq, Deserialize)]
 pub enum OperationError {
     NotAuthorized,
     NonExistentItem,
@@ -46,6 +171,17 @@ pub enum OperationError {
     Unknown(String),
 }
 
+// A token that still has to be listed in the canister registry, with the input of its `add` call
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingOperation {
+    pub principal_id: Principal,
+    pub input: AddTokenInput,
+    pub trusted_source: Option<Principal>,
+    pub created_at: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
 #[derive(Deserialize, CandidType)]
 pub enum RegistryResponse {
     Ok(Option<String>),
@@ -53,3 +189,5 @@ pub enum RegistryResponse {
 }
 
 pub const CANISTER_REGISTRY_ID: &'static str = "curr3-vaaaa-aaaah-abbdq-cai";
+pub const DESCRIPTION_LIMIT: usize = 1200;
+pub const NAME_LIMIT: usize = 120;
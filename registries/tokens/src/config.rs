@@ -0,0 +1,66 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::str::FromStr;
+
+use crate::common_types::*;
+use crate::details::is_token_detail;
+use crate::management::is_admin;
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            name_limit: NAME_LIMIT,
+            description_limit: DESCRIPTION_LIMIT,
+            canister_registry: Some(Principal::from_str(CANISTER_REGISTRY_ID).unwrap()),
+            allowed_detail_keys: vec![],
+        }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), OperationError> {
+        let mut keys = self.allowed_detail_keys.clone();
+        keys.sort();
+        keys.dedup();
+
+        if self.name_limit == 0
+            || self.description_limit == 0
+            || keys.len() != self.allowed_detail_keys.len()
+            || keys
+                .iter()
+                .any(|key| key.is_empty() || is_token_detail(key))
+        {
+            return Err(OperationError::BadParameters);
+        }
+
+        Ok(())
+    }
+}
+
+// Without a configuration at init, tokens keep being listed in the mainnet canister registry
+pub fn configure(config: Option<Config>) {
+    if let Some(config) = config {
+        if let Err(err) = config.validate() {
+            ic::trap(&format!("Invalid configuration: {:?}", err));
+        }
+
+        ic::store(config);
+    }
+}
+
+#[query]
+pub fn get_config() -> &'static Config {
+    ic::get::<Config>()
+}
+
+#[update]
+pub fn set_config(config: Config) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    config.validate()?;
+    ic::store(config);
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use ic_kit::candid::Nat;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::common_types::*;
+
+// The details that every token has, in the order they are stored in, the optional details follow them
+pub const TOKEN_DETAILS: [&str; 6] = [
+    "symbol",
+    "standard",
+    "total_supply",
+    "verified",
+    "decimals",
+    "fee",
+];
+
+pub const INDEX_CANISTER_KEY: &str = "index_canister";
+pub const ICRC1_STANDARD: &str = "ICRC1";
+pub const ICRC2_STANDARD: &str = "ICRC2";
+
+pub const SYMBOL_LIMIT: usize = 20;
+pub const MAX_DECIMALS: u8 = 18;
+
+pub fn is_token_detail(key: &str) -> bool {
+    TOKEN_DETAILS.contains(&key) || key == INDEX_CANISTER_KEY
+}
+
+// The ICRC standards are stored with a single spelling so that wallets can match on them, e.g.
+// "icrc-1" is stored as "ICRC1". The other standards are kept as they are.
+pub fn normalize_standard(standard: &str) -> String {
+    let normalized = standard
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .collect::<String>()
+        .to_uppercase();
+
+    match normalized.as_str() {
+        ICRC1_STANDARD | ICRC2_STANDARD => normalized,
+        _ => standard.to_string(),
+    }
+}
+
+pub fn is_icrc(standard: &str) -> bool {
+    matches!(standard, ICRC1_STANDARD | ICRC2_STANDARD)
+}
+
+fn text(value: &DetailValue) -> Result<String, OperationError> {
+    match value {
+        DetailValue::Text(text) if !text.trim().is_empty() => Ok(text.clone()),
+        _ => Err(OperationError::BadParameters),
+    }
+}
+
+// Big naturals are sent as `U64`, or as decimal `Text` when they do not fit
+fn nat(value: &DetailValue) -> Result<Nat, OperationError> {
+    match value {
+        DetailValue::U64(value) => Ok(Nat::from(*value)),
+        DetailValue::Text(text) if text.chars().all(|c| c.is_ascii_digit()) => {
+            Nat::from_str(text).map_err(|_| OperationError::BadParameters)
+        }
+        _ => Err(OperationError::BadParameters),
+    }
+}
+
+fn nat_value(nat: Nat) -> DetailValue {
+    match u64::try_from(&nat.0) {
+        Ok(value) => DetailValue::U64(value),
+        Err(_) => DetailValue::Text(nat.0.to_str_radix(10)),
+    }
+}
+
+impl TryFrom<&[(String, DetailValue)]> for TokenDetails {
+    type Error = OperationError;
+
+    // The details can come in any order, but each of them exactly once
+    fn try_from(details: &[(String, DetailValue)]) -> Result<Self, Self::Error> {
+        let find_optional = |key: &str| -> Result<Option<&DetailValue>, OperationError> {
+            let mut values = details.iter().filter(|(other, _)| other == key);
+            match (values.next(), values.next()) {
+                (value, None) => Ok(value.map(|(_, value)| value)),
+                _ => Err(OperationError::BadParameters),
+            }
+        };
+        let find = |key: &str| find_optional(key)?.ok_or(OperationError::BadParameters);
+
+        let symbol = text(find("symbol")?)?;
+        if symbol.len() > SYMBOL_LIMIT {
+            return Err(OperationError::BadParameters);
+        }
+
+        // The registry sets the verified status, the submitted one is optional and ignored
+        let verified = match find_optional("verified")? {
+            None | Some(DetailValue::False) => false,
+            Some(DetailValue::True) => true,
+            Some(_) => return Err(OperationError::BadParameters),
+        };
+
+        let decimals = match find("decimals")? {
+            DetailValue::U64(decimals) if *decimals <= MAX_DECIMALS as u64 => *decimals as u8,
+            _ => return Err(OperationError::BadParameters),
+        };
+
+        let standard = normalize_standard(&text(find("standard")?)?);
+        let index_canister = match find_optional(INDEX_CANISTER_KEY)? {
+            None => None,
+            Some(DetailValue::Principal(index_canister)) if is_icrc(&standard) => {
+                Some(*index_canister)
+            }
+            Some(_) => return Err(OperationError::BadParameters),
+        };
+
+        Ok(TokenDetails {
+            symbol,
+            standard,
+            total_supply: nat(find("total_supply")?)?,
+            verified,
+            decimals,
+            fee: nat(find("fee")?)?,
+            index_canister,
+        })
+    }
+}
+
+impl From<TokenDetails> for Vec<(String, DetailValue)> {
+    fn from(details: TokenDetails) -> Self {
+        let verified = match details.verified {
+            true => DetailValue::True,
+            false => DetailValue::False,
+        };
+
+        let mut result = vec![
+            (String::from("symbol"), DetailValue::Text(details.symbol)),
+            (
+                String::from("standard"),
+                DetailValue::Text(details.standard),
+            ),
+            (
+                String::from("total_supply"),
+                nat_value(details.total_supply),
+            ),
+            (String::from("verified"), verified),
+            (
+                String::from("decimals"),
+                DetailValue::U64(details.decimals as u64),
+            ),
+            (String::from("fee"), nat_value(details.fee)),
+        ];
+
+        if let Some(index_canister) = details.index_canister {
+            result.push((
+                String::from(INDEX_CANISTER_KEY),
+                DetailValue::Principal(index_canister),
+            ));
+        }
+
+        result
+    }
+}
+
+pub fn set_verified(details: &mut [(String, DetailValue)], verified: bool) {
+    for (key, value) in details.iter_mut() {
+        if key == "verified" {
+            *value = match verified {
+                true => DetailValue::True,
+                false => DetailValue::False,
+            };
+        }
+    }
+}
+
+// Checks the token details and the other allowed keys, and returns the typed details with the details
+// in their stored order: the token details first, followed by the other keys
+pub fn validate_details(
+    details: &[(String, DetailValue)],
+    allowed_keys: &[String],
+) -> Result<(TokenDetails, Vec<(String, DetailValue)>), OperationError> {
+    let token_details = TokenDetails::try_from(details)?;
+    let mut normalized = Vec::from(token_details.clone());
+
+    for (key, value) in details.iter().filter(|(key, _)| !is_token_detail(key)) {
+        if !allowed_keys.contains(key) || normalized.iter().any(|(other, _)| other == key) {
+            return Err(OperationError::BadParameters);
+        }
+
+        normalized.push((key.clone(), value.clone()));
+    }
+
+    Ok((token_details, normalized))
+}
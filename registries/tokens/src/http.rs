@@ -0,0 +1,52 @@
+use ic_kit::macros::*;
+
+use crate::common_types::*;
+use crate::logos::get_logo_by_path;
+use crate::tokenlist::render_token_list;
+
+fn header(name: &str, value: &str) -> HeaderField {
+    (String::from(name), String::from(value))
+}
+
+fn response(status_code: u16, content_type: &str, body: Vec<u8>) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![
+            header("Content-Type", content_type),
+            header("Access-Control-Allow-Origin", "*"),
+        ],
+        body,
+    }
+}
+
+fn error(status_code: u16, message: &str) -> HttpResponse {
+    response(status_code, "text/plain", message.as_bytes().to_vec())
+}
+
+#[query]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    if request.method != "GET" {
+        return error(405, "Method not allowed");
+    }
+
+    let path = request.url.split('?').next().unwrap_or_default();
+
+    if path == "/tokenlist.json" {
+        return response(200, "application/json", render_token_list());
+    }
+
+    match get_logo_by_path(path) {
+        Some(logo) => {
+            let mut response = response(200, &logo.content_type, logo.data.clone());
+            response.headers.extend([
+                header("Cache-Control", "public, max-age=31536000, immutable"),
+                header(
+                    "Content-Security-Policy",
+                    "default-src 'none'; style-src 'unsafe-inline'",
+                ),
+            ]);
+            response
+        }
+        None => error(404, "Not found"),
+    }
+}
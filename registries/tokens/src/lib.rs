@@ -1,5 +1,15 @@
 mod common_types;
+mod config;
+mod details;
+mod http;
+mod logos;
 mod management;
+mod metadata;
+mod reconciliation;
+mod relationships;
+mod symbols;
 mod tests;
+mod tokenlist;
 mod tokens;
 mod upgrade;
+mod verification;
@@ -0,0 +1,377 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+use crate::tokenlist::token_edited;
+use crate::tokens::TokenRegistry;
+
+// A logo sent in a single call, bigger logos are sent in chunks
+pub const MAX_INLINE_LOGO_SIZE: usize = 256 * 1024;
+pub const MAX_LOGO_SIZE: usize = 1024 * 1024;
+// The logos are kept on the heap and saved whole on upgrade, so their total size is capped
+pub const MAX_TOTAL_LOGO_SIZE: usize = 32 * 1024 * 1024;
+
+pub const PNG: &str = "image/png";
+pub const SVG: &str = "image/svg+xml";
+pub const WEBP: &str = "image/webp";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// The elements of a plain image, anything else (scripts, animations, links, embedded documents...)
+// is rejected
+const SVG_ELEMENTS: [&str; 36] = [
+    "svg",
+    "g",
+    "defs",
+    "symbol",
+    "use",
+    "title",
+    "desc",
+    "metadata",
+    "path",
+    "rect",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "text",
+    "tspan",
+    "textpath",
+    "lineargradient",
+    "radialgradient",
+    "stop",
+    "pattern",
+    "clippath",
+    "mask",
+    "marker",
+    "filter",
+    "feblend",
+    "fecolormatrix",
+    "fecomposite",
+    "feflood",
+    "fegaussianblur",
+    "femerge",
+    "femergenode",
+    "feoffset",
+    "fedropshadow",
+    "femorphology",
+];
+
+// The declarations that can expand entities or load other resources
+const UNSAFE_DECLARATIONS: [&str; 3] = ["<!doctype", "<!entity", "<?xml-stylesheet"];
+
+// The values that can run code or load other documents, matched once the entities are decoded
+const UNSAFE_VALUES: [&str; 4] = ["javascript:", "vbscript:", "data:text/html", "@import"];
+
+// The logo of each token, replaced when a new one is uploaded
+#[derive(Default)]
+pub struct Logos(HashMap<Principal, Logo>);
+
+impl Logos {
+    pub fn archive(&mut self) -> Vec<(Principal, Logo)> {
+        let map = std::mem::take(&mut self.0);
+        map.into_iter().collect()
+    }
+
+    pub fn load(&mut self, archive: Vec<(Principal, Logo)>) {
+        self.0 = archive.into_iter().collect();
+    }
+
+    pub fn get(&self, principal_id: &Principal) -> Option<&Logo> {
+        self.0.get(principal_id)
+    }
+
+    // Whether the logo fits in the budget once it replaces the current logo of the token
+    pub fn fits(&self, principal_id: &Principal, size: usize) -> bool {
+        let total: usize = self
+            .0
+            .iter()
+            .filter(|(id, _)| *id != principal_id)
+            .map(|(_, logo)| logo.data.len())
+            .sum();
+        total + size <= MAX_TOTAL_LOGO_SIZE
+    }
+
+    pub fn insert(&mut self, principal_id: Principal, logo: Logo) {
+        self.0.insert(principal_id, logo);
+    }
+
+    pub fn remove(&mut self, principal_id: &Principal) {
+        self.0.remove(principal_id);
+    }
+}
+
+// The chunks of the logos that are being uploaded, they are not kept across upgrades
+#[derive(Default)]
+pub struct PendingLogos(HashMap<Principal, Vec<u8>>);
+
+fn extension(content_type: &str) -> &'static str {
+    match content_type {
+        PNG => "png",
+        SVG => "svg",
+        _ => "webp",
+    }
+}
+
+// The path changes with the content, so that a reviewed URL always serves the same image
+pub fn logo_path(principal_id: &Principal, logo: &Logo) -> String {
+    format!(
+        "/logos/{}/{}.{}",
+        principal_id,
+        logo.sha256,
+        extension(&logo.content_type)
+    )
+}
+
+pub fn logo_url(principal_id: &Principal, logo: &Logo) -> String {
+    format!(
+        "https://{}.raw.ic0.app{}",
+        ic::id(),
+        logo_path(principal_id, logo)
+    )
+}
+
+fn is_event_handler(attribute: &str) -> bool {
+    let name = attribute.trim_end();
+    name.len() > 2 && name.starts_with("on") && name.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+// Only references inside the document are allowed, e.g. `href="#gradient"`
+fn is_external_reference(value: &str) -> bool {
+    let value =
+        value.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '('));
+    !value.starts_with('#')
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "colon" => Some(':'),
+        "tab" => Some('\t'),
+        "newline" => Some('\n'),
+        _ => {
+            let code = match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+            };
+            code.and_then(char::from_u32)
+        }
+    }
+}
+
+// Replaces the character references, e.g. `&#106;` or `&#x6a;`, by the characters they stand for
+fn decode_entities(svg: &str) -> String {
+    let mut decoded = String::with_capacity(svg.len());
+    let mut rest = svg;
+
+    while let Some(index) = rest.find('&') {
+        decoded.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '#'))
+            .unwrap_or(rest.len());
+        match decode_entity(&rest[..end]) {
+            Some(c) => {
+                decoded.push(c);
+                rest = rest[end..].strip_prefix(';').unwrap_or(&rest[end..]);
+            }
+            None => decoded.push('&'),
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+// The names of the elements opened in the document
+fn element_names(svg: &str) -> impl Iterator<Item = &str> {
+    svg.split('<').skip(1).filter_map(|tag| {
+        let end = tag
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, ':' | '-' | '_' | '.')))
+            .unwrap_or(tag.len());
+        match end {
+            0 => None,
+            _ => Some(&tag[..end]),
+        }
+    })
+}
+
+// SVGs are rejected rather than rewritten when they contain anything that is not a plain image: elements
+// that are not allowed, event handlers, or references to scripts and other resources
+pub fn sanitize_svg(logo: &[u8]) -> Result<(), OperationError> {
+    let svg = std::str::from_utf8(logo)
+        .map_err(|_| OperationError::BadParameters)?
+        .to_lowercase();
+
+    if !svg.contains("<svg")
+        || UNSAFE_DECLARATIONS
+            .iter()
+            .any(|declaration| svg.contains(declaration))
+        || element_names(&svg).any(|name| !SVG_ELEMENTS.contains(&name))
+    {
+        return Err(OperationError::BadParameters);
+    }
+
+    // Entities can only hide values, the markup is checked before they are decoded. Browsers also
+    // ignore the whitespace inside URLs, e.g. `java&#9;script:`.
+    let decoded = decode_entities(&svg).to_lowercase();
+    let compact: String = decoded.chars().filter(|c| !c.is_whitespace()).collect();
+    // CSS escapes could hide them as well, e.g. `u\72 l(`
+    if compact.contains('\\') || UNSAFE_VALUES.iter().any(|value| compact.contains(value)) {
+        return Err(OperationError::BadParameters);
+    }
+
+    for (index, _) in decoded.match_indices('=') {
+        let attribute = decoded[..index]
+            .trim_end()
+            .rsplit(|c: char| c.is_whitespace() || c == '<')
+            .next()
+            .unwrap_or_default();
+        let references = attribute == "href" || attribute.ends_with(":href") || attribute == "src";
+
+        if is_event_handler(attribute)
+            || (references && is_external_reference(&decoded[index + 1..]))
+        {
+            return Err(OperationError::BadParameters);
+        }
+    }
+
+    for (index, _) in decoded.match_indices("url(") {
+        if is_external_reference(&decoded[index + 4..]) {
+            return Err(OperationError::BadParameters);
+        }
+    }
+
+    Ok(())
+}
+
+// The content has to be of the declared type
+pub fn validate_logo(content_type: &str, logo: &[u8]) -> Result<(), OperationError> {
+    if logo.is_empty() || logo.len() > MAX_LOGO_SIZE {
+        return Err(OperationError::BadParameters);
+    }
+
+    let valid = match content_type {
+        PNG => logo.starts_with(PNG_SIGNATURE),
+        WEBP => logo.len() > 12 && &logo[..4] == b"RIFF" && &logo[8..12] == b"WEBP",
+        SVG => {
+            sanitize_svg(logo)?;
+            true
+        }
+        _ => false,
+    };
+
+    match valid {
+        true => Ok(()),
+        false => Err(OperationError::BadParameters),
+    }
+}
+
+fn check_upload(principal_id: &Principal) -> Result<(), OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    match ic::get::<TokenRegistry>().get_info(principal_id) {
+        Some(_) => Ok(()),
+        None => Err(OperationError::NonExistentItem),
+    }
+}
+
+// Stores the logo and points the thumbnail of the token at it
+fn store_logo(
+    principal_id: Principal,
+    content_type: String,
+    data: Vec<u8>,
+) -> Result<String, OperationError> {
+    validate_logo(&content_type, &data)?;
+    if !ic::get::<Logos>().fits(&principal_id, data.len()) {
+        return Err(OperationError::BadParameters);
+    }
+
+    let sha256 = Sha256::digest(&data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let logo = Logo {
+        content_type,
+        sha256,
+        data,
+    };
+    let url = logo_url(&principal_id, &logo);
+
+    ic::get_mut::<Logos>().insert(principal_id, logo);
+    ic::get_mut::<TokenRegistry>().set_thumbnail(&principal_id, url.clone());
+    token_edited();
+    Ok(url)
+}
+
+// Uploads a logo in one call and returns its URL
+#[update]
+pub fn set_logo(
+    principal_id: Principal,
+    content_type: String,
+    logo: Vec<u8>,
+) -> Result<String, OperationError> {
+    check_upload(&principal_id)?;
+
+    if logo.len() > MAX_INLINE_LOGO_SIZE {
+        return Err(OperationError::BadParameters);
+    }
+
+    store_logo(principal_id, content_type, logo)
+}
+
+// Appends a chunk to the logo of the token, `commit_logo` stores it
+#[update]
+pub fn upload_logo_chunk(principal_id: Principal, chunk: Vec<u8>) -> Result<(), OperationError> {
+    check_upload(&principal_id)?;
+
+    let pending = &mut ic::get_mut::<PendingLogos>().0;
+    let logo = pending.entry(principal_id).or_default();
+    if logo.len() + chunk.len() > MAX_LOGO_SIZE {
+        pending.remove(&principal_id);
+        return Err(OperationError::BadParameters);
+    }
+
+    logo.extend(chunk);
+    Ok(())
+}
+
+// Stores the uploaded chunks as the logo of the token and returns its URL
+#[update]
+pub fn commit_logo(
+    principal_id: Principal,
+    content_type: String,
+) -> Result<String, OperationError> {
+    check_upload(&principal_id)?;
+
+    match ic::get_mut::<PendingLogos>().0.remove(&principal_id) {
+        Some(logo) => store_logo(principal_id, content_type, logo),
+        None => Err(OperationError::BadParameters),
+    }
+}
+
+// The logo served at the path, older logos of the token are not served
+pub fn get_logo_by_path(path: &str) -> Option<&'static Logo> {
+    let principal_id = path
+        .strip_prefix("/logos/")
+        .and_then(|path| path.split('/').next())
+        .and_then(|text| Principal::from_text(text).ok())?;
+    let logo = ic::get::<Logos>().get(&principal_id)?;
+
+    match logo_path(&principal_id, logo) == path {
+        true => Some(logo),
+        false => None,
+    }
+}
@@ -0,0 +1,306 @@
+use ic_kit::candid::{CandidType, Deserialize, Int, Nat, Principal};
+use ic_kit::*;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::common_types::*;
+use crate::details::normalize_standard;
+
+// The details that a token canister reports about itself, the fee is not reported by every standard
+// and only ICRC ledgers report their ledger details
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub decimals: u8,
+    pub fee: Option<Nat>,
+    pub ledger: Option<IcrcLedger>,
+}
+
+pub type MetadataResponse = Pin<Box<dyn Future<Output = Result<TokenMetadata, String>>>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MetadataEndpoint {
+    Dip20,
+    Icrc1,
+    Ext,
+}
+
+impl MetadataEndpoint {
+    // Tokens of the other standards are not cross-checked
+    pub fn of(standard: &str) -> Option<MetadataEndpoint> {
+        let standard: String = standard
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_lowercase();
+
+        match standard.as_str() {
+            "dip20" => Some(MetadataEndpoint::Dip20),
+            // ICRC-2 ledgers also implement ICRC-1
+            "icrc1" | "icrc2" => Some(MetadataEndpoint::Icrc1),
+            "ext" => Some(MetadataEndpoint::Ext),
+            _ => None,
+        }
+    }
+
+    pub fn method(&self) -> &'static str {
+        match self {
+            MetadataEndpoint::Dip20 => "getMetadata",
+            MetadataEndpoint::Icrc1 => "icrc1_metadata",
+            MetadataEndpoint::Ext => "metadata",
+        }
+    }
+}
+
+// Reads the metadata of a token canister, implemented by a mock in the tests
+pub trait MetadataSource {
+    fn metadata(&self, token: Principal, endpoint: MetadataEndpoint) -> MetadataResponse;
+}
+
+// Only the fields that are compared are decoded
+#[derive(CandidType, Deserialize)]
+struct Dip20Metadata {
+    symbol: String,
+    decimals: u8,
+    fee: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+enum Icrc1Value {
+    Nat(Nat),
+    Int(Int),
+    Text(String),
+    Blob(#[serde(with = "serde_bytes")] Vec<u8>),
+}
+
+#[derive(CandidType, Deserialize)]
+struct ArchiveInfo {
+    canister_id: Principal,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ExtFungible {
+    symbol: String,
+    decimals: u8,
+}
+
+#[derive(CandidType, Deserialize)]
+enum ExtMetadata {
+    #[serde(rename = "fungible")]
+    Fungible(ExtFungible),
+    #[serde(rename = "nonfungible")]
+    NonFungible(Option<Vec<u8>>),
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum ExtCommonError {
+    InvalidToken(String),
+    Other(String),
+}
+
+#[derive(CandidType, Deserialize)]
+enum ExtMetadataResponse {
+    #[serde(rename = "ok")]
+    Ok(ExtMetadata),
+    #[serde(rename = "err")]
+    Err(ExtCommonError),
+}
+
+// The EXT token identifier of the first token of a canister, which is the token of fungible canisters
+pub fn ext_token_identifier(canister: &Principal) -> String {
+    let mut bytes = b"\x0Atid".to_vec();
+    bytes.extend_from_slice(canister.as_slice());
+    bytes.extend_from_slice(&0u32.to_be_bytes());
+    Principal::from_slice(&bytes).to_text()
+}
+
+fn icrc1_metadata(entries: Vec<(String, Icrc1Value)>) -> Result<TokenMetadata, String> {
+    let find = |key: &str| {
+        entries
+            .iter()
+            .find(|(other, _)| other == key)
+            .map(|(_, value)| value)
+    };
+
+    let symbol = match find("icrc1:symbol") {
+        Some(Icrc1Value::Text(symbol)) => symbol.clone(),
+        _ => return Err(String::from("icrc1:symbol is missing")),
+    };
+    let decimals = match find("icrc1:decimals") {
+        Some(Icrc1Value::Nat(decimals)) => {
+            u8::try_from(&decimals.0).map_err(|_| String::from("icrc1:decimals is out of range"))?
+        }
+        _ => return Err(String::from("icrc1:decimals is missing")),
+    };
+    let fee = match find("icrc1:fee") {
+        Some(Icrc1Value::Nat(fee)) => Some(fee.clone()),
+        _ => None,
+    };
+    let logo = match find("icrc1:logo") {
+        Some(Icrc1Value::Text(logo)) => Some(logo.clone()),
+        _ => None,
+    };
+
+    Ok(TokenMetadata {
+        symbol,
+        decimals,
+        fee,
+        ledger: Some(IcrcLedger {
+            logo,
+            ..IcrcLedger::default()
+        }),
+    })
+}
+
+async fn icrc1_ledger(token: Principal) -> Result<TokenMetadata, String> {
+    let (entries,): (Vec<(String, Icrc1Value)>,) = ic::call(token, "icrc1_metadata", ())
+        .await
+        .map_err(|(_code, msg)| msg)?;
+    let (supported_standards,): (Vec<SupportedStandard>,) =
+        ic::call(token, "icrc1_supported_standards", ())
+            .await
+            .map_err(|(_code, msg)| msg)?;
+    let (minting_account,): (Option<Account>,) = ic::call(token, "icrc1_minting_account", ())
+        .await
+        .map_err(|(_code, msg)| msg)?;
+
+    // Not every ledger lists its archives
+    let archive_canisters = match ic::call::<_, (Vec<ArchiveInfo>,), _>(token, "archives", ()).await
+    {
+        Ok((archives,)) => archives
+            .into_iter()
+            .map(|archive| archive.canister_id)
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    let mut metadata = icrc1_metadata(entries)?;
+    metadata.ledger = metadata.ledger.map(|ledger| IcrcLedger {
+        supported_standards,
+        minting_account,
+        archive_canisters,
+        ..ledger
+    });
+
+    Ok(metadata)
+}
+
+pub struct TokenCanister;
+
+impl MetadataSource for TokenCanister {
+    fn metadata(&self, token: Principal, endpoint: MetadataEndpoint) -> MetadataResponse {
+        Box::pin(async move {
+            let method = endpoint.method();
+
+            match endpoint {
+                MetadataEndpoint::Dip20 => {
+                    let (metadata,): (Dip20Metadata,) = ic::call(token, method, ())
+                        .await
+                        .map_err(|(_code, msg)| msg)?;
+
+                    Ok(TokenMetadata {
+                        symbol: metadata.symbol,
+                        decimals: metadata.decimals,
+                        fee: Some(metadata.fee),
+                        ledger: None,
+                    })
+                }
+                MetadataEndpoint::Icrc1 => icrc1_ledger(token).await,
+                MetadataEndpoint::Ext => {
+                    let (response,): (ExtMetadataResponse,) =
+                        ic::call(token, method, (ext_token_identifier(&token),))
+                            .await
+                            .map_err(|(_code, msg)| msg)?;
+
+                    match response {
+                        ExtMetadataResponse::Ok(ExtMetadata::Fungible(metadata)) => {
+                            Ok(TokenMetadata {
+                                symbol: metadata.symbol,
+                                decimals: metadata.decimals,
+                                fee: None,
+                                ledger: None,
+                            })
+                        }
+                        ExtMetadataResponse::Ok(ExtMetadata::NonFungible(_)) => {
+                            Err(String::from("the canister is not fungible"))
+                        }
+                        ExtMetadataResponse::Err(err) => Err(format!("{:?}", err)),
+                    }
+                }
+            }
+        })
+    }
+}
+
+// Compares the submitted details with the ones the token canister reports
+pub fn compare_metadata(
+    details: &TokenDetails,
+    metadata: &TokenMetadata,
+) -> Result<(), OperationError> {
+    let mismatch = |field: &str, declared: String, reported: String| {
+        Err(OperationError::Unknown(format!(
+            "The {} does not match the token canister: {} was submitted but the canister reports {}",
+            field, declared, reported
+        )))
+    };
+
+    if details.symbol != metadata.symbol {
+        return mismatch("symbol", details.symbol.clone(), metadata.symbol.clone());
+    }
+    if details.decimals != metadata.decimals {
+        return mismatch(
+            "decimals",
+            details.decimals.to_string(),
+            metadata.decimals.to_string(),
+        );
+    }
+    if let Some(fee) = &metadata.fee {
+        if details.fee != *fee {
+            return mismatch("fee", details.fee.0.to_string(), fee.0.to_string());
+        }
+    }
+
+    // An ICRC ledger has to list the standard it was submitted with
+    if let Some(ledger) = &metadata.ledger {
+        let supported = ledger
+            .supported_standards
+            .iter()
+            .any(|standard| normalize_standard(&standard.name) == details.standard);
+        if !supported {
+            return Err(OperationError::Unknown(format!(
+                "The ledger does not list {} in its supported standards",
+                details.standard
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the ledger details of ICRC tokens, with the index canister that was submitted
+pub async fn check_metadata<M: MetadataSource>(
+    source: &M,
+    token: Principal,
+    details: &TokenDetails,
+) -> Result<Option<IcrcLedger>, OperationError> {
+    let endpoint = match MetadataEndpoint::of(&details.standard) {
+        Some(endpoint) => endpoint,
+        None => return Ok(None),
+    };
+
+    let metadata = source.metadata(token, endpoint).await.map_err(|err| {
+        OperationError::Unknown(format!(
+            "The token canister did not answer {}: {}",
+            endpoint.method(),
+            err
+        ))
+    })?;
+
+    compare_metadata(details, &metadata)?;
+
+    Ok(metadata.ledger.map(|ledger| IcrcLedger {
+        index_canister: details.index_canister,
+        ..ledger
+    }))
+}
@@ -0,0 +1,146 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::collections::BTreeMap;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+
+pub const TOKEN_CATEGORY: &str = "Token";
+
+// The tokens that still have to be listed in the canister registry, keyed by their principal id.
+// A newer version of a token replaces the one that is still pending.
+#[derive(Default)]
+pub struct PendingOperations(BTreeMap<Principal, PendingOperation>);
+
+impl PendingOperations {
+    pub fn archive(&mut self) -> Vec<PendingOperation> {
+        let map = std::mem::take(&mut self.0);
+        map.into_values().collect()
+    }
+
+    pub fn load(&mut self, archive: Vec<PendingOperation>) {
+        self.0 = archive
+            .into_iter()
+            .map(|operation| (operation.principal_id, operation))
+            .collect();
+    }
+
+    pub fn push(&mut self, input: AddTokenInput, trusted_source: Option<Principal>) {
+        let operation = PendingOperation {
+            principal_id: input.principal_id,
+            input,
+            trusted_source,
+            created_at: ic::time(),
+            attempts: 0,
+            last_error: None,
+        };
+
+        self.0.insert(operation.principal_id, operation);
+    }
+
+    pub fn remove(&mut self, principal_id: &Principal) {
+        self.0.remove(principal_id);
+    }
+
+    pub fn get_all(&self) -> Vec<&PendingOperation> {
+        self.0.values().collect()
+    }
+
+    // Records the outcome of an attempt, unless the operation was replaced while the call was running
+    fn complete(&mut self, attempted: &PendingOperation, result: Result<(), String>) {
+        let operation = match self.0.get_mut(&attempted.principal_id) {
+            Some(operation) if operation == attempted => operation,
+            _ => return,
+        };
+
+        match result {
+            Ok(()) => {
+                self.0.remove(&attempted.principal_id);
+            }
+            Err(err) => {
+                operation.attempts += 1;
+                operation.last_error = Some(err);
+            }
+        }
+    }
+}
+
+// The canister registry only keeps the category of the tokens
+pub fn schedule(token: &AddTokenInput, trusted_source: Option<Principal>) {
+    if ic::get::<Config>().canister_registry.is_some() {
+        let mut input = token.clone();
+        input.details = vec![(
+            "category".to_string(),
+            DetailValue::Text(TOKEN_CATEGORY.to_string()),
+        )];
+        ic::get_mut::<PendingOperations>().push(input, trusted_source);
+    }
+}
+
+async fn execute(canister_registry: Principal, operation: &PendingOperation) -> Result<(), String> {
+    let response: Result<(RegistryResponse,), _> = ic::call(
+        canister_registry,
+        "add",
+        (
+            Some(operation.trusted_source.unwrap_or(ic::id())),
+            operation.input.clone(),
+        ),
+    )
+    .await;
+
+    match response {
+        Ok((RegistryResponse::Ok(None),)) => Ok(()),
+        // The token was only queued for review, it is not listed until an admin accepts it
+        Ok((RegistryResponse::Ok(Some(msg)),)) => Err(msg),
+        Ok((RegistryResponse::Err(err),)) => Err(format!("{:?}", err)),
+        Err((_code, msg)) => Err(msg),
+    }
+}
+
+// Lists the pending token in the canister registry, a failed attempt stays queued for a retry
+pub async fn sync(principal_id: Principal) -> Result<(), String> {
+    let operation = match ic::get::<PendingOperations>().0.get(&principal_id) {
+        Some(operation) => operation.clone(),
+        None => return Ok(()),
+    };
+
+    let result = match ic::get::<Config>().canister_registry {
+        Some(canister_registry) => execute(canister_registry, &operation).await,
+        None => Err(String::from("No canister registry is configured")),
+    };
+    ic::get_mut::<PendingOperations>().complete(&operation, result.clone());
+    result
+}
+
+fn pending_operations() -> Vec<PendingOperation> {
+    ic::get::<PendingOperations>()
+        .get_all()
+        .into_iter()
+        .cloned()
+        .collect()
+}
+
+#[query]
+pub fn get_pending_operations() -> Result<Vec<PendingOperation>, OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    Ok(pending_operations())
+}
+
+// Returns the operations that failed again
+#[update]
+pub async fn retry_pending_operations() -> Result<Vec<PendingOperation>, OperationError> {
+    if !is_admin(&ic::caller()) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    let principal_ids: Vec<Principal> = ic::get::<PendingOperations>().0.keys().cloned().collect();
+    for principal_id in principal_ids {
+        sync(principal_id).await.ok();
+    }
+
+    Ok(pending_operations())
+}
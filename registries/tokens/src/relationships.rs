@@ -0,0 +1,90 @@
+use ic_kit::candid::Principal;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::common_types::*;
+
+pub const CHAIN_LIMIT: usize = 32;
+pub const CONTRACT_ADDRESS_LIMIT: usize = 128;
+
+impl TokenRelationship {
+    // The tokens of the registry that the relationship points at
+    pub fn principals(&self) -> Vec<Principal> {
+        match self {
+            TokenRelationship::Wraps(WrappedAsset::Token(token)) => vec![*token],
+            TokenRelationship::Wraps(WrappedAsset::Cycles) => vec![],
+            TokenRelationship::BridgedFrom { .. } => vec![],
+            TokenRelationship::LpOf { token0, token1 } => vec![*token0, *token1],
+        }
+    }
+}
+
+pub fn relationships(token: &Token) -> &[TokenRelationship] {
+    token.relationships.as_deref().unwrap_or_default()
+}
+
+fn is_valid_text(text: &str, limit: usize) -> bool {
+    !text.trim().is_empty() && text.len() <= limit
+}
+
+// A token cannot be related to itself, and each relationship is listed once
+pub fn validate_relationships(
+    principal_id: &Principal,
+    relationships: &[TokenRelationship],
+) -> Result<(), OperationError> {
+    for (index, relationship) in relationships.iter().enumerate() {
+        let valid = match relationship {
+            TokenRelationship::BridgedFrom {
+                chain,
+                contract_address,
+            } => {
+                is_valid_text(chain, CHAIN_LIMIT)
+                    && is_valid_text(contract_address, CONTRACT_ADDRESS_LIMIT)
+            }
+            TokenRelationship::LpOf { token0, token1 } => token0 != token1,
+            TokenRelationship::Wraps(_) => true,
+        };
+
+        if !valid
+            || relationship.principals().contains(principal_id)
+            || relationships[..index].contains(relationship)
+        {
+            return Err(OperationError::BadParameters);
+        }
+    }
+
+    Ok(())
+}
+
+// The tokens that point at each token through their relationships
+#[derive(Default)]
+pub struct RelationIndex(BTreeMap<Principal, BTreeSet<Principal>>);
+
+impl RelationIndex {
+    pub fn insert(&mut self, token: &Token) {
+        for relationship in relationships(token) {
+            for related in relationship.principals() {
+                self.0
+                    .entry(related)
+                    .or_default()
+                    .insert(token.principal_id);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, token: &Token) {
+        for relationship in relationships(token) {
+            for related in relationship.principals() {
+                if let Some(principals) = self.0.get_mut(&related) {
+                    principals.remove(&token.principal_id);
+                    if principals.is_empty() {
+                        self.0.remove(&related);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, principal_id: &Principal) -> impl Iterator<Item = &Principal> {
+        self.0.get(principal_id).into_iter().flatten()
+    }
+}
@@ -0,0 +1,55 @@
+use ic_kit::candid::Principal;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::common_types::*;
+
+pub fn normalize_symbol(symbol: &str) -> String {
+    symbol.trim().to_lowercase()
+}
+
+// The symbol is read from the details directly, the tokens that were listed before the details were
+// typed do not always have valid details
+pub fn symbol(details: &[(String, DetailValue)]) -> Option<&String> {
+    details.iter().find_map(|(key, value)| match value {
+        DetailValue::Text(symbol) if key == "symbol" => Some(symbol),
+        _ => None,
+    })
+}
+
+pub fn is_verified(details: &[(String, DetailValue)]) -> bool {
+    details
+        .iter()
+        .any(|(key, value)| key == "verified" && *value == DetailValue::True)
+}
+
+// The tokens of each symbol, keyed by the normalised symbol. Several tokens can share a symbol, e.g.
+// the same asset on two standards.
+#[derive(Default)]
+pub struct SymbolIndex(BTreeMap<String, BTreeSet<Principal>>);
+
+impl SymbolIndex {
+    pub fn insert(&mut self, token: &Token) {
+        if let Some(symbol) = symbol(&token.details) {
+            self.0
+                .entry(normalize_symbol(symbol))
+                .or_default()
+                .insert(token.principal_id);
+        }
+    }
+
+    pub fn remove(&mut self, token: &Token) {
+        if let Some(symbol) = symbol(&token.details) {
+            let key = normalize_symbol(symbol);
+            if let Some(principals) = self.0.get_mut(&key) {
+                principals.remove(&token.principal_id);
+                if principals.is_empty() {
+                    self.0.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, symbol: &str) -> impl Iterator<Item = &Principal> {
+        self.0.get(&normalize_symbol(symbol)).into_iter().flatten()
+    }
+}
@@ -265,3 +265,925 @@
 //         assert!(token.is_none());
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use crate::common_types::*;
+    use crate::config::{get_config, set_config};
+    use crate::details::validate_details;
+    use crate::http::http_request;
+    use crate::logos::{
+        commit_logo, sanitize_svg, set_logo, upload_logo_chunk, Logos, MAX_INLINE_LOGO_SIZE,
+        MAX_LOGO_SIZE, MAX_TOTAL_LOGO_SIZE, PNG, SVG,
+    };
+    use crate::metadata::{
+        ext_token_identifier, MetadataEndpoint, MetadataResponse, MetadataSource, TokenMetadata,
+    };
+    use crate::reconciliation::{get_pending_operations, retry_pending_operations};
+    use crate::tokenlist::{get_token_list_version, iso_timestamp};
+    use crate::tokens::{
+        add_with, get, get_by_symbol, get_related, get_token_details, init, remove,
+    };
+    use crate::verification::{get_verification_history, unverify_token, verify_token};
+    use ic_kit::candid::Nat;
+    use ic_kit::*;
+    use std::cell::{Cell, RefCell};
+    use std::collections::BTreeSet;
+    use std::convert::TryFrom;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    fn token_input(details: Vec<(String, DetailValue)>) -> AddTokenInput {
+        let mut token_details = vec![
+            (
+                String::from("symbol"),
+                DetailValue::Text(String::from("WICP")),
+            ),
+            (
+                String::from("standard"),
+                DetailValue::Text(String::from("DIP20")),
+            ),
+            (String::from("total_supply"), DetailValue::U64(1000)),
+            (String::from("verified"), DetailValue::True),
+            (String::from("decimals"), DetailValue::U64(8)),
+            (String::from("fee"), DetailValue::U64(0)),
+        ];
+        token_details.extend(details);
+
+        AddTokenInput {
+            name: String::from("Wrapped ICP"),
+            description: String::from("Wrapped IPC description"),
+            thumbnail: String::from("https://logo.com"),
+            frontend: Some(String::from("https://website.com")),
+            principal_id: mock_principals::xtc(),
+            details: token_details,
+            relationships: None,
+        }
+    }
+
+    // Answers with the given metadata, or rejects the call when there is none
+    struct MockMetadata(Option<TokenMetadata>);
+
+    impl MetadataSource for MockMetadata {
+        fn metadata(&self, _token: Principal, _endpoint: MetadataEndpoint) -> MetadataResponse {
+            let metadata = self.0.clone();
+            Box::pin(async move { metadata.ok_or(String::from("call rejected")) })
+        }
+    }
+
+    // Runs the function while the metadata is awaited, like a message that is handled during the call
+    struct MockMetadataThen(MockMetadata, fn());
+
+    impl MetadataSource for MockMetadataThen {
+        fn metadata(&self, token: Principal, endpoint: MetadataEndpoint) -> MetadataResponse {
+            let response = self.0.metadata(token, endpoint);
+            let then = self.1;
+            Box::pin(async move {
+                then();
+                response.await
+            })
+        }
+    }
+
+    // Invalid details are rejected before the canister is called
+    fn reported(token_info: &AddTokenInput) -> MockMetadata {
+        let allowed_keys = &get_config().allowed_detail_keys;
+
+        MockMetadata(
+            validate_details(&token_info.details, allowed_keys)
+                .ok()
+                .map(|(details, _)| TokenMetadata {
+                    symbol: details.symbol,
+                    decimals: details.decimals,
+                    fee: Some(details.fee),
+                    ledger: None,
+                }),
+        )
+    }
+
+    // Adds a token whose canister reports the submitted details
+    async fn add_token(token_info: AddTokenInput) -> Result<(), OperationError> {
+        add_with(&reported(&token_info), None, token_info).await
+    }
+
+    // The canister registry is skipped so that `add` does not call it
+    fn local_config() -> Config {
+        Config {
+            canister_registry: None,
+            ..Config::default()
+        }
+    }
+
+    fn token_with_symbol(principal_id: Principal, symbol: &str) -> AddTokenInput {
+        let mut token_info = token_input(vec![]);
+        token_info.principal_id = principal_id;
+        token_info.details[0].1 = DetailValue::Text(String::from(symbol));
+        token_info
+    }
+
+    type Listed = Rc<RefCell<BTreeSet<Principal>>>;
+
+    // Answers `add` like the canister registry and keeps the added tokens in `listed`. Calls are
+    // rejected while `offline` is set and tokens are only queued for review while `reviewing` is set.
+    fn canister_registry(
+        listed: Listed,
+        offline: Rc<Cell<bool>>,
+        reviewing: Rc<Cell<bool>>,
+    ) -> RawHandler {
+        RawHandler::raw(Box::new(move |_, args, _, _| {
+            if offline.get() {
+                return Err((RejectionCode::CanisterError, String::from("offline")));
+            }
+
+            let (_, input): (Option<Principal>, AddTokenInput) = candid::decode_args(args).unwrap();
+            let response = match reviewing.get() {
+                true => RegistryResponse::Ok(Some(String::from(
+                    "The entry has been flagged for review",
+                ))),
+                false => {
+                    listed.borrow_mut().insert(input.principal_id);
+                    RegistryResponse::Ok(None)
+                }
+            };
+
+            Ok(candid::encode_args((response,)).unwrap())
+        }))
+    }
+
+    fn http_get(url: &str) -> HttpResponse {
+        http_request(HttpRequest {
+            method: String::from("GET"),
+            url: String::from(url),
+            headers: vec![],
+            body: vec![],
+        })
+    }
+
+    fn related(principal_id: Principal) -> Vec<Principal> {
+        let mut related: Vec<Principal> = get_related(principal_id)
+            .iter()
+            .map(|token| token.principal_id)
+            .collect();
+        related.sort();
+        related
+    }
+
+    fn version() -> (u64, u64, u64) {
+        let version = get_token_list_version();
+        (version.major, version.minor, version.patch)
+    }
+
+    #[async_std::test]
+    async fn test_config_is_set_at_init_and_by_admins() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+        assert_eq!(get_config(), &local_config());
+
+        // Either limit is enough to reject a token
+        let mut token_info = token_input(vec![]);
+        token_info.description = "x".repeat(DESCRIPTION_LIMIT + 1);
+        assert!(add_token(token_info).await.is_err());
+
+        let twitter = (
+            String::from("twitter"),
+            DetailValue::Text(String::from("@wicp")),
+        );
+        assert!(add_token(token_input(vec![twitter.clone()])).await.is_err());
+
+        let config = Config {
+            allowed_detail_keys: vec![String::from("twitter")],
+            ..local_config()
+        };
+        assert!(set_config(config.clone()).is_ok());
+        assert!(
+            add_token(token_input(vec![twitter.clone(), twitter.clone()]))
+                .await
+                .is_err()
+        );
+        assert!(add_token(token_input(vec![twitter])).await.is_ok());
+        assert!(get(mock_principals::xtc()).is_some());
+
+        // The required details cannot be listed as extra keys
+        let invalid = Config {
+            allowed_detail_keys: vec![String::from("symbol")],
+            ..local_config()
+        };
+        assert!(set_config(invalid).is_err());
+
+        ctx.update_caller(mock_principals::bob());
+        assert!(set_config(config).is_err());
+    }
+
+    #[test]
+    fn test_token_details_are_typed_in_any_order() {
+        let mut details = token_input(vec![]).details;
+        details.reverse();
+
+        let (token_details, normalized) = validate_details(&details, &[]).unwrap();
+        assert_eq!(
+            token_details,
+            TokenDetails {
+                symbol: String::from("WICP"),
+                standard: String::from("DIP20"),
+                total_supply: Nat::from(1000u64),
+                verified: true,
+                decimals: 8,
+                fee: Nat::from(0u64),
+                index_canister: None,
+            }
+        );
+        assert_eq!(normalized, token_input(vec![]).details);
+
+        // Supplies that do not fit in a u64 are kept as decimal text
+        let supply = "1000000000000000000000000";
+        details[3].1 = DetailValue::Text(String::from(supply));
+        let (token_details, normalized) = validate_details(&details, &[]).unwrap();
+        assert_eq!(token_details.total_supply, Nat::from_str(supply).unwrap());
+        assert_eq!(
+            TokenDetails::try_from(normalized.as_slice()),
+            Ok(token_details)
+        );
+
+        let invalid_details = [
+            ("decimals", DetailValue::U64(19)),
+            ("fee", DetailValue::I64(-1)),
+            ("total_supply", DetailValue::Text(String::from("1_000"))),
+            ("verified", DetailValue::Text(String::from("true"))),
+            ("symbol", DetailValue::Text(String::new())),
+        ];
+        for (key, value) in invalid_details.iter() {
+            let mut details = token_input(vec![]).details;
+            let detail = details.iter_mut().find(|(other, _)| other == key).unwrap();
+            detail.1 = value.clone();
+            assert_eq!(
+                validate_details(&details, &[]),
+                Err(OperationError::BadParameters)
+            );
+        }
+
+        let mut details = token_input(vec![]).details;
+        details.pop();
+        assert_eq!(
+            validate_details(&details, &[]),
+            Err(OperationError::BadParameters)
+        );
+        details.push((
+            String::from("symbol"),
+            DetailValue::Text(String::from("XTC")),
+        ));
+        assert_eq!(
+            validate_details(&details, &[]),
+            Err(OperationError::BadParameters)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_add_stores_the_details_in_order() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let mut token_info = token_input(vec![]);
+        token_info.details.swap(0, 5);
+        assert!(add_token(token_info).await.is_ok());
+
+        // The submitted verified status is ignored
+        let mut expected = token_input(vec![]).details;
+        expected[3].1 = DetailValue::False;
+        let token = get(mock_principals::xtc()).unwrap();
+        assert_eq!(token.details, expected);
+        assert_eq!(
+            get_token_details(mock_principals::xtc()).unwrap().symbol,
+            "WICP"
+        );
+    }
+
+    #[async_std::test]
+    async fn test_symbols_are_indexed_and_protected() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let unverified = token_with_symbol(mock_principals::bob(), "xtc");
+        assert!(add_token(unverified.clone()).await.is_ok());
+        assert!(add_token(token_with_symbol(mock_principals::xtc(), "XTC"))
+            .await
+            .is_ok());
+        assert!(verify_token(mock_principals::xtc(), String::from("Audited")).is_ok());
+
+        // The verified token is listed first
+        let tokens = get_by_symbol(String::from(" Xtc "));
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].principal_id, mock_principals::xtc());
+
+        // Another unverified token cannot take the symbol, the existing one can still be updated
+        assert!(add_token(token_with_symbol(mock_principals::john(), "XTC"))
+            .await
+            .is_err());
+        assert!(add_token(unverified).await.is_ok());
+
+        // Renaming and removing a token updates the index
+        assert!(add_token(token_with_symbol(mock_principals::bob(), "BOB"))
+            .await
+            .is_ok());
+        assert!(remove(None, mock_principals::xtc()).is_ok());
+        assert!(get_by_symbol(String::from("XTC")).is_empty());
+        assert_eq!(get_by_symbol(String::from("bob")).len(), 1);
+        assert!(add_token(token_with_symbol(mock_principals::john(), "XTC"))
+            .await
+            .is_ok());
+    }
+
+    #[async_std::test]
+    async fn test_verification_is_governed_by_admins() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let reason = String::from("Audited");
+        assert_eq!(
+            verify_token(mock_principals::xtc(), reason.clone()),
+            Err(OperationError::NonExistentItem)
+        );
+
+        // The submitted status is not taken
+        assert!(add_token(token_input(vec![])).await.is_ok());
+        assert!(!get_token_details(mock_principals::xtc()).unwrap().verified);
+
+        assert_eq!(
+            verify_token(mock_principals::xtc(), String::from(" ")),
+            Err(OperationError::BadParameters)
+        );
+        assert!(verify_token(mock_principals::xtc(), reason.clone()).is_ok());
+        assert_eq!(
+            verify_token(mock_principals::xtc(), reason.clone()),
+            Err(OperationError::BadParameters)
+        );
+
+        let token = get(mock_principals::xtc()).unwrap();
+        let record = token.verification.clone().unwrap();
+        assert!(record.verified);
+        assert_eq!(record.changed_by, mock_principals::alice());
+        assert_eq!(record.reason, reason);
+        assert!(get_token_details(mock_principals::xtc()).unwrap().verified);
+
+        // Updating the token keeps the status, even when it is submitted as unverified
+        let mut token_info = token_input(vec![]);
+        token_info.details[3].1 = DetailValue::False;
+        assert!(add_token(token_info).await.is_ok());
+        assert!(get_token_details(mock_principals::xtc()).unwrap().verified);
+
+        assert!(unverify_token(mock_principals::xtc(), String::from("Compromised")).is_ok());
+        assert!(!get_token_details(mock_principals::xtc()).unwrap().verified);
+
+        let history = get_verification_history(mock_principals::xtc());
+        assert_eq!(history.len(), 2);
+        assert!(history[0].verified);
+        assert!(!history[1].verified);
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(
+            verify_token(mock_principals::xtc(), reason),
+            Err(OperationError::NotAuthorized)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_symbol_is_checked_again_after_the_metadata_call() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        assert!(add_token(token_with_symbol(mock_principals::xtc(), "XTC"))
+            .await
+            .is_ok());
+
+        // The symbol is taken by a verified token while the metadata is checked
+        let token_info = token_with_symbol(mock_principals::john(), "XTC");
+        let verify = MockMetadataThen(reported(&token_info), || {
+            verify_token(mock_principals::xtc(), String::from("Audited")).unwrap();
+        });
+        assert!(add_with(&verify, None, token_info).await.is_err());
+        assert!(get(mock_principals::john()).is_none());
+        assert_eq!(get_by_symbol(String::from("XTC")).len(), 1);
+    }
+
+    #[async_std::test]
+    async fn test_verification_made_during_an_update_is_kept() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        assert!(add_token(token_input(vec![])).await.is_ok());
+
+        let token_info = token_input(vec![]);
+        let verify = MockMetadataThen(reported(&token_info), || {
+            verify_token(mock_principals::xtc(), String::from("Audited")).unwrap();
+        });
+        assert!(add_with(&verify, None, token_info).await.is_ok());
+        let token = get(mock_principals::xtc()).unwrap();
+        assert!(token.verification.as_ref().unwrap().verified);
+        assert!(get_token_details(mock_principals::xtc()).unwrap().verified);
+    }
+
+    #[async_std::test]
+    async fn test_add_cross_checks_the_token_metadata() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let metadata = TokenMetadata {
+            symbol: String::from("WICP"),
+            decimals: 8,
+            fee: Some(Nat::from(0u64)),
+            ledger: None,
+        };
+
+        let mismatches = vec![
+            TokenMetadata {
+                symbol: String::from("ICP"),
+                ..metadata.clone()
+            },
+            TokenMetadata {
+                decimals: 18,
+                ..metadata.clone()
+            },
+            TokenMetadata {
+                fee: Some(Nat::from(10_000u64)),
+                ..metadata.clone()
+            },
+        ];
+        for mismatch in mismatches {
+            let result = add_with(&MockMetadata(Some(mismatch)), None, token_input(vec![])).await;
+            assert!(matches!(result, Err(OperationError::Unknown(_))));
+        }
+
+        let result = add_with(&MockMetadata(None), None, token_input(vec![])).await;
+        assert!(
+            matches!(result, Err(OperationError::Unknown(message)) if message.contains("getMetadata"))
+        );
+        assert!(get(mock_principals::xtc()).is_none());
+
+        // The fee is not compared when the standard does not report it
+        let unreported_fee = TokenMetadata {
+            fee: None,
+            ..metadata.clone()
+        };
+        assert!(add_with(
+            &MockMetadata(Some(unreported_fee)),
+            None,
+            token_input(vec![])
+        )
+        .await
+        .is_ok());
+
+        // Tokens of a standard without a known metadata method are not checked
+        let mut token_info = token_input(vec![]);
+        token_info.principal_id = mock_principals::bob();
+        token_info.details[1].1 = DetailValue::Text(String::from("XRC"));
+        assert!(add_with(&MockMetadata(None), None, token_info)
+            .await
+            .is_ok());
+
+        assert_eq!(
+            MetadataEndpoint::of("ICRC-1"),
+            Some(MetadataEndpoint::Icrc1)
+        );
+        assert_eq!(MetadataEndpoint::of("ext"), Some(MetadataEndpoint::Ext));
+
+        // The identifier of the first token is the prefix, the canister id and a zero index
+        let canister = mock_principals::xtc();
+        let identifier = Principal::from_text(ext_token_identifier(&canister)).unwrap();
+        let mut expected = b"\x0Atid".to_vec();
+        expected.extend_from_slice(canister.as_slice());
+        expected.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(identifier.as_slice(), expected.as_slice());
+    }
+
+    #[async_std::test]
+    async fn test_canister_registry_additions_are_retried() {
+        let listed = Listed::default();
+        let offline = Rc::new(Cell::new(true));
+        let reviewing = Rc::new(Cell::new(false));
+
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .with_handler(canister_registry(
+                listed.clone(),
+                offline.clone(),
+                reviewing.clone(),
+            ))
+            .inject();
+
+        init(Some(Config {
+            canister_registry: Some(mock_principals::john()),
+            ..Config::default()
+        }));
+
+        // The token is listed locally and the failed call stays pending
+        assert!(add_token(token_input(vec![])).await.is_ok());
+        // The context stays in reply callback mode after an inter-canister call
+        ctx.call_state_reset();
+        assert!(get(mock_principals::xtc()).is_some());
+
+        let pending = get_pending_operations().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+        assert_eq!(pending[0].last_error, Some(String::from("offline")));
+        assert_eq!(
+            pending[0].input.details,
+            vec![(
+                String::from("category"),
+                DetailValue::Text(String::from("Token"))
+            )]
+        );
+
+        // A token that is only queued for review is not listed yet
+        offline.set(false);
+        reviewing.set(true);
+        let pending = retry_pending_operations().await.unwrap();
+        ctx.call_state_reset();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(
+            pending[0].last_error,
+            Some(String::from("The entry has been flagged for review"))
+        );
+        assert!(listed.borrow().is_empty());
+
+        reviewing.set(false);
+        assert_eq!(retry_pending_operations().await, Ok(vec![]));
+        ctx.call_state_reset();
+        assert!(listed.borrow().contains(&mock_principals::xtc()));
+
+        // Removing a token drops its pending operation
+        offline.set(true);
+        assert!(add_token(token_with_symbol(mock_principals::bob(), "BOB"))
+            .await
+            .is_ok());
+        ctx.call_state_reset();
+        assert_eq!(get_pending_operations().unwrap().len(), 1);
+        assert!(remove(None, mock_principals::bob()).is_ok());
+        assert!(get_pending_operations().unwrap().is_empty());
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(get_pending_operations(), Err(OperationError::NotAuthorized));
+    }
+
+    #[async_std::test]
+    async fn test_icrc_tokens_record_their_ledger() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let mut token_info = token_input(vec![(
+            String::from("index_canister"),
+            DetailValue::Principal(mock_principals::bob()),
+        )]);
+        token_info.details[1].1 = DetailValue::Text(String::from("icrc-2"));
+
+        let standard = |name: &str| SupportedStandard {
+            name: String::from(name),
+            url: format!("https://github.com/dfinity/ICRC-1/standards/{}", name),
+        };
+        let ledger = IcrcLedger {
+            supported_standards: vec![standard("ICRC-1")],
+            minting_account: Some(Account {
+                owner: mock_principals::john(),
+                subaccount: None,
+            }),
+            logo: Some(String::from("data:image/png;base64,AAAA")),
+            index_canister: None,
+            archive_canisters: vec![mock_principals::alice()],
+        };
+        let metadata = TokenMetadata {
+            symbol: String::from("WICP"),
+            decimals: 8,
+            fee: Some(Nat::from(0u64)),
+            ledger: Some(ledger.clone()),
+        };
+
+        // The ledger has to list the standard the token was submitted with
+        let result = add_with(
+            &MockMetadata(Some(metadata.clone())),
+            None,
+            token_info.clone(),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(OperationError::Unknown(message)) if message.contains("ICRC2"))
+        );
+
+        let ledger = IcrcLedger {
+            supported_standards: vec![standard("ICRC-1"), standard("ICRC-2")],
+            ..ledger
+        };
+        let metadata = TokenMetadata {
+            ledger: Some(ledger.clone()),
+            ..metadata
+        };
+        assert!(add_with(&MockMetadata(Some(metadata)), None, token_info)
+            .await
+            .is_ok());
+
+        let token = get(mock_principals::xtc()).unwrap();
+        assert_eq!(
+            token.ledger,
+            Some(IcrcLedger {
+                index_canister: Some(mock_principals::bob()),
+                ..ledger
+            })
+        );
+        let details = get_token_details(mock_principals::xtc()).unwrap();
+        assert_eq!(details.standard, "ICRC2");
+        assert_eq!(details.index_canister, Some(mock_principals::bob()));
+
+        // Only ICRC tokens have an index canister
+        let token_info = token_input(vec![(
+            String::from("index_canister"),
+            DetailValue::Principal(mock_principals::bob()),
+        )]);
+        assert_eq!(
+            add_token(token_info).await,
+            Err(OperationError::BadParameters)
+        );
+    }
+
+    #[test]
+    fn test_svgs_are_sanitized() {
+        let safe =
+            br##"<svg xmlns="http://www.w3.org/2000/svg"><defs><linearGradient id="g"/></defs>
+            <rect fill="url(#g)" width="10" height="10"/><use href="#g"/></svg>"##;
+        assert!(sanitize_svg(safe).is_ok());
+
+        // Entities in the text and comments are fine
+        let text = b"<svg><!-- logo --><text x=\"0\">A &amp; B &#169;</text></svg>";
+        assert!(sanitize_svg(text).is_ok());
+
+        let unsafe_svgs: [&[u8]; 14] = [
+            b"<svg><script>alert(1)</script></svg>",
+            b"<svg><rect onload=\"alert(1)\"/></svg>",
+            b"<svg><image href=\"https://logo.com/x.png\"/></svg>",
+            b"<svg><rect style=\"fill: url(https://logo.com/x)\"/></svg>",
+            b"<!DOCTYPE svg [<!ENTITY x SYSTEM \"file:///etc\">]><svg>&x;</svg>",
+            b"<html></html>",
+            // Animations can rewrite the attributes of other elements
+            b"<svg><a href=\"#a\"><animate attributeName=\"href\" values=\"&#106;avascript:alert(1)\"/></a></svg>",
+            b"<svg><set attributeName=\"fill\" to=\"red\"/></svg>",
+            b"<svg><rect style=\"fill: &#x75;rl(https://logo.com/x)\"/></svg>",
+            b"<svg><text>java&#9;script&colon;alert(1)</text></svg>",
+            // Whitespace around `=` does not hide the attribute
+            b"<svg onload =\"alert(1)\"></svg>",
+            b"<svg><use href =\n \"https://logo.com/x.svg#a\"/></svg>",
+            // CSS escapes can hide `url(`
+            b"<svg><rect style=\"fill: u\\72 l(https://logo.com/x)\"/></svg>",
+            // Style sheets are not allowed at all
+            b"<svg><style>rect { fill: red }</style></svg>",
+        ];
+        for svg in unsafe_svgs {
+            assert_eq!(sanitize_svg(svg), Err(OperationError::BadParameters));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_logos_are_stored_and_served() {
+        let ctx = MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let png = b"\x89PNG\r\n\x1a\nimage".to_vec();
+        assert_eq!(
+            set_logo(mock_principals::xtc(), String::from(PNG), png.clone()),
+            Err(OperationError::NonExistentItem)
+        );
+        assert!(add_token(token_input(vec![])).await.is_ok());
+
+        // The content has to match its type and the inline size cap
+        assert!(set_logo(mock_principals::xtc(), String::from(SVG), png.clone()).is_err());
+        assert!(set_logo(
+            mock_principals::xtc(),
+            String::from(PNG),
+            vec![0; MAX_INLINE_LOGO_SIZE + 1]
+        )
+        .is_err());
+
+        let url = set_logo(mock_principals::xtc(), String::from(PNG), png.clone()).unwrap();
+        assert_eq!(get(mock_principals::xtc()).unwrap().thumbnail, url);
+        let path = url
+            .splitn(4, '/')
+            .nth(3)
+            .map(|path| format!("/{}", path))
+            .unwrap();
+        assert!(path.starts_with(&format!("/logos/{}/", mock_principals::xtc())));
+        assert!(path.ends_with(".png"));
+
+        let response = http_get(&path);
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body, png);
+        assert!(response
+            .headers
+            .contains(&(String::from("Content-Type"), String::from(PNG))));
+        assert_eq!(http_get("/logos/other.png").status_code, 404);
+
+        // An update keeps the uploaded logo
+        assert!(add_token(token_input(vec![])).await.is_ok());
+        assert_eq!(get(mock_principals::xtc()).unwrap().thumbnail, url);
+
+        // A new logo gets a new URL, the old one is not served anymore
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>".to_vec();
+        assert!(upload_logo_chunk(mock_principals::xtc(), svg[..10].to_vec()).is_ok());
+        assert!(upload_logo_chunk(mock_principals::xtc(), svg[10..].to_vec()).is_ok());
+        let svg_url = commit_logo(mock_principals::xtc(), String::from(SVG)).unwrap();
+        assert_ne!(svg_url, url);
+        assert_eq!(http_get(&path).status_code, 404);
+        assert!(commit_logo(mock_principals::xtc(), String::from(SVG)).is_err());
+
+        assert!(remove(None, mock_principals::xtc()).is_ok());
+        let svg_path = svg_url.splitn(4, '/').nth(3).unwrap();
+        assert_eq!(http_get(&format!("/{}", svg_path)).status_code, 404);
+
+        ctx.update_caller(mock_principals::bob());
+        assert_eq!(
+            upload_logo_chunk(mock_principals::xtc(), svg),
+            Err(OperationError::NotAuthorized)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_logos_share_a_size_budget() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let png = b"\x89PNG\r\n\x1a\nimage".to_vec();
+        // The logos of all the tokens share a budget
+        assert!(add_token(token_input(vec![])).await.is_ok());
+        let url = set_logo(mock_principals::xtc(), String::from(PNG), png.clone()).unwrap();
+        // Other logos fill the budget but 64 bytes
+        for i in 0..(MAX_TOTAL_LOGO_SIZE / MAX_LOGO_SIZE) as u8 {
+            let size = if i == 0 {
+                MAX_LOGO_SIZE - 64
+            } else {
+                MAX_LOGO_SIZE
+            };
+            let logo = Logo {
+                content_type: String::from(PNG),
+                sha256: String::new(),
+                data: vec![0; size],
+            };
+            ic::get_mut::<Logos>().insert(Principal::from_slice(&[i]), logo);
+        }
+        let mut big = png.clone();
+        big.resize(MAX_LOGO_SIZE, 0);
+        assert_eq!(
+            set_logo(
+                mock_principals::xtc(),
+                String::from(PNG),
+                big[..MAX_INLINE_LOGO_SIZE].to_vec()
+            ),
+            Err(OperationError::BadParameters)
+        );
+        assert_eq!(get(mock_principals::xtc()).unwrap().thumbnail, url);
+        // A logo can still be replaced by one that fits
+        assert!(set_logo(
+            mock_principals::xtc(),
+            String::from(PNG),
+            big[..64].to_vec()
+        )
+        .is_ok());
+    }
+
+    #[async_std::test]
+    async fn test_related_tokens() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+
+        let icp = mock_principals::bob();
+        let wicp = mock_principals::xtc();
+        let lp = mock_principals::john();
+        assert!(add_token(token_with_symbol(icp, "ICP")).await.is_ok());
+
+        let mut wicp_info = token_with_symbol(wicp, "WICP");
+        wicp_info.relationships = Some(vec![TokenRelationship::Wraps(WrappedAsset::Token(wicp))]);
+        assert_eq!(
+            add_token(wicp_info.clone()).await,
+            Err(OperationError::BadParameters)
+        );
+        let wraps_icp = TokenRelationship::Wraps(WrappedAsset::Token(icp));
+        wicp_info.relationships = Some(vec![wraps_icp.clone(), wraps_icp.clone()]);
+        assert!(add_token(wicp_info.clone()).await.is_err());
+        wicp_info.relationships = Some(vec![wraps_icp.clone()]);
+        assert!(add_token(wicp_info.clone()).await.is_ok());
+
+        let mut lp_info = token_with_symbol(lp, "LP");
+        lp_info.relationships = Some(vec![
+            TokenRelationship::LpOf {
+                token0: icp,
+                token1: wicp,
+            },
+            TokenRelationship::BridgedFrom {
+                chain: String::from("Ethereum"),
+                contract_address: String::from("0x6b17"),
+            },
+        ]);
+        assert!(add_token(lp_info).await.is_ok());
+
+        let mut expected = vec![wicp, lp];
+        expected.sort();
+        assert_eq!(related(icp), expected);
+        let mut expected = vec![icp, lp];
+        expected.sort();
+        assert_eq!(related(wicp), expected);
+
+        // An update without relationships keeps them, an empty list removes them
+        wicp_info.relationships = None;
+        assert!(add_token(wicp_info.clone()).await.is_ok());
+        assert_eq!(
+            get(wicp).unwrap().relationships,
+            Some(vec![wraps_icp.clone()])
+        );
+        wicp_info.relationships = Some(vec![]);
+        assert!(add_token(wicp_info).await.is_ok());
+        assert_eq!(related(icp), vec![lp]);
+
+        assert!(remove(None, lp).is_ok());
+        assert!(related(icp).is_empty());
+        assert!(related(wicp).is_empty());
+    }
+
+    #[test]
+    fn test_timestamps_are_iso_8601() {
+        assert_eq!(iso_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            iso_timestamp(1_646_092_799 * 1_000_000_000),
+            "2022-02-28T23:59:59Z"
+        );
+        assert_eq!(
+            iso_timestamp(951_782_400 * 1_000_000_000),
+            "2000-02-29T00:00:00Z"
+        );
+    }
+
+    #[async_std::test]
+    async fn test_token_list_is_versioned() {
+        MockContext::new()
+            .with_caller(mock_principals::alice())
+            .inject();
+
+        init(Some(local_config()));
+        assert_eq!(version(), (1, 0, 0));
+
+        assert!(add_token(token_with_symbol(mock_principals::bob(), "ICP"))
+            .await
+            .is_ok());
+        assert!(add_token(token_input(vec![])).await.is_ok());
+        assert_eq!(version(), (1, 2, 0));
+
+        // Edits bump the patch version, a rejected edit does not
+        assert!(add_token(token_input(vec![])).await.is_ok());
+        assert!(verify_token(mock_principals::xtc(), String::from("Audited")).is_ok());
+        assert!(verify_token(mock_principals::xtc(), String::from("Audited")).is_err());
+        assert_eq!(version(), (1, 2, 2));
+
+        let response = http_get("/tokenlist.json");
+        assert_eq!(response.status_code, 200);
+        let json = String::from_utf8(response.body).unwrap();
+        assert!(json.starts_with(&format!(
+            "{{\"name\":\"DAB Token List\",\"timestamp\":\"{}\",\"version\":{{\"major\":1,\"minor\":2,\"patch\":2}}",
+            iso_timestamp(get_token_list_version().timestamp)
+        )));
+        assert!(json.contains(&format!(
+            "{{\"address\":\"{}\",\"name\":\"Wrapped ICP\",\"symbol\":\"WICP\",\"decimals\":8,\"standard\":\"DIP20\",\"logoURI\":\"https://logo.com\",\"tags\":[\"verified\"]}}",
+            mock_principals::xtc()
+        )));
+
+        assert!(remove(None, mock_principals::bob()).is_ok());
+        assert_eq!(version(), (2, 0, 0));
+        assert!(!String::from_utf8(http_get("/tokenlist.json").body)
+            .unwrap()
+            .contains("\"symbol\":\"ICP\""));
+    }
+}
@@ -0,0 +1,167 @@
+use ic_kit::macros::*;
+use ic_kit::*;
+use serde::Serialize;
+use std::convert::TryFrom;
+
+use crate::common_types::*;
+use crate::tokens::TokenRegistry;
+
+pub const TOKEN_LIST_NAME: &str = "DAB Token List";
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+impl Default for TokenListVersion {
+    fn default() -> Self {
+        Self {
+            major: 1,
+            minor: 0,
+            patch: 0,
+            timestamp: 0,
+        }
+    }
+}
+
+impl TokenListVersion {
+    // A removed token can break the consumers of the list
+    pub fn bump_major(&mut self) {
+        self.major += 1;
+        self.minor = 0;
+        self.patch = 0;
+        self.timestamp = ic::time();
+    }
+
+    pub fn bump_minor(&mut self) {
+        self.minor += 1;
+        self.patch = 0;
+        self.timestamp = ic::time();
+    }
+
+    pub fn bump_patch(&mut self) {
+        self.patch += 1;
+        self.timestamp = ic::time();
+    }
+}
+
+// The list starts at 1.0.0 when the canister is installed
+pub fn start_token_list() {
+    ic::get_mut::<TokenListVersion>().timestamp = ic::time();
+}
+
+#[derive(Serialize)]
+struct ListVersion {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListToken<'a> {
+    address: String,
+    name: &'a str,
+    symbol: String,
+    decimals: u8,
+    standard: String,
+    #[serde(rename = "logoURI")]
+    logo_uri: &'a str,
+    tags: Vec<&'static str>,
+}
+
+// The schema of the Uniswap token lists, without the chain ids
+#[derive(Serialize)]
+struct TokenList<'a> {
+    name: &'static str,
+    timestamp: String,
+    version: ListVersion,
+    tokens: Vec<ListToken<'a>>,
+}
+
+// e.g. 2022-01-31T12:00:00Z
+pub fn iso_timestamp(nanos: u64) -> String {
+    let seconds = nanos / NANOS_PER_SECOND;
+    let time = seconds % SECONDS_PER_DAY;
+
+    // The civil date of a number of days since 1970-01-01, by Howard Hinnant
+    let days = (seconds / SECONDS_PER_DAY) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+// The tokens whose details cannot be read are left out, ordered by their principal
+pub fn render_token_list() -> Vec<u8> {
+    let version = ic::get::<TokenListVersion>();
+    let mut tokens: Vec<&Token> = ic::get::<TokenRegistry>().get_all();
+    tokens.sort_by_key(|token| token.principal_id);
+
+    let tokens = tokens
+        .into_iter()
+        .filter_map(|token| {
+            let details = TokenDetails::try_from(token.details.as_slice()).ok()?;
+            Some(ListToken {
+                address: token.principal_id.to_text(),
+                name: &token.name,
+                symbol: details.symbol,
+                decimals: details.decimals,
+                standard: details.standard,
+                logo_uri: &token.thumbnail,
+                tags: match details.verified {
+                    true => vec!["verified"],
+                    false => vec![],
+                },
+            })
+        })
+        .collect();
+
+    let list = TokenList {
+        name: TOKEN_LIST_NAME,
+        timestamp: iso_timestamp(version.timestamp),
+        version: ListVersion {
+            major: version.major,
+            minor: version.minor,
+            patch: version.patch,
+        },
+        tokens,
+    };
+
+    serde_json::to_vec(&list).unwrap()
+}
+
+// Lets the consumers of the list check whether it changed without downloading it
+#[query]
+pub fn get_token_list_version() -> &'static TokenListVersion {
+    ic::get::<TokenListVersion>()
+}
+
+pub fn token_added() {
+    ic::get_mut::<TokenListVersion>().bump_minor();
+}
+
+pub fn token_removed() {
+    ic::get_mut::<TokenListVersion>().bump_major();
+}
+
+pub fn token_edited() {
+    ic::get_mut::<TokenListVersion>().bump_patch();
+}
@@ -2,37 +2,101 @@ use ic_kit::candid::Principal;
 use ic_kit::macros::*;
 use ic_kit::*;
 use std::any::Any;
-use std::collections::HashMap;
-use std::str::FromStr;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
 use validator::validate_url;
 
 use crate::common_types::*;
+use crate::config::configure;
+use crate::details::{set_verified, validate_details};
+use crate::logos::{logo_url, Logos};
 use crate::management::*;
+use crate::metadata::{check_metadata, MetadataSource, TokenCanister};
+use crate::reconciliation::{schedule, sync, PendingOperations};
+use crate::relationships::{relationships, validate_relationships, RelationIndex};
+use crate::symbols::{is_verified, normalize_symbol, symbol, SymbolIndex};
+use crate::tokenlist::{start_token_list, token_added, token_edited, token_removed};
+use crate::verification::Verifications;
 
 pub trait Object {
     fn type_name(&self) -> &str;
     fn as_any(&self) -> &dyn Any;
 }
 
+// (registry map, symbol index, relation index)
 #[derive(Default)]
-pub struct TokenRegistry(HashMap<Principal, Token>);
+pub struct TokenRegistry(HashMap<Principal, Token>, SymbolIndex, RelationIndex);
 
 impl TokenRegistry {
     pub fn archive(&mut self) -> Vec<(Principal, Token)> {
         let map = std::mem::replace(&mut self.0, HashMap::new());
+        self.1 = SymbolIndex::default();
+        self.2 = RelationIndex::default();
         map.into_iter().collect()
     }
 
     pub fn load(&mut self, archive: Vec<(Principal, Token)>) {
         self.0 = archive.into_iter().collect();
+        for token in self.0.values() {
+            self.1.insert(token);
+            self.2.insert(token);
+        }
+    }
+
+    // An unverified token cannot claim the symbol of a verified token, a common way to impersonate it.
+    // Tokens that already had the symbol can still be updated.
+    pub fn check_symbol(&self, token_info: &AddTokenInput) -> Result<(), OperationError> {
+        let verified = ic::get::<Verifications>().is_verified(&token_info.principal_id);
+        let claimed = match symbol(&token_info.details) {
+            Some(claimed) if !verified => normalize_symbol(claimed),
+            _ => return Ok(()),
+        };
+
+        let current = self
+            .0
+            .get(&token_info.principal_id)
+            .and_then(|token| symbol(&token.details));
+        if current.map(|current| normalize_symbol(current)) == Some(claimed.clone()) {
+            return Ok(());
+        }
+
+        let holder = self.get_by_symbol(&claimed).into_iter().find(|token| {
+            token.principal_id != token_info.principal_id && is_verified(&token.details)
+        });
+
+        match holder {
+            Some(token) => Err(OperationError::Unknown(format!(
+                "The symbol is held by the verified token {}",
+                token.principal_id
+            ))),
+            None => Ok(()),
+        }
     }
 
     pub fn add(
         &mut self,
         caller: &Principal,
         token_info: AddTokenInput,
+        ledger: Option<IcrcLedger>,
     ) -> Result<(), OperationError> {
         let token = self.0.get(&token_info.principal_id);
+        let verification = ic::get::<Verifications>()
+            .latest(&token_info.principal_id)
+            .cloned();
+        // Only `verify_token` and `unverify_token` change the verified status
+        let mut details = token_info.details;
+        set_verified(
+            &mut details,
+            matches!(&verification, Some(record) if record.verified),
+        );
+        // An uploaded logo cannot be replaced by another thumbnail
+        let thumbnail = match ic::get::<Logos>().get(&token_info.principal_id) {
+            Some(logo) => logo_url(&token_info.principal_id, logo),
+            None => token_info.thumbnail,
+        };
+        let relationships = token_info
+            .relationships
+            .or_else(|| token.and_then(|token| token.relationships.clone()));
 
         // If its an update, check if the caller matches the submitter or if its an admin
         if token.is_some() && !is_admin(caller) && token.unwrap().submitter != *caller {
@@ -43,34 +107,46 @@ impl TokenRegistry {
             let updated_token = Token {
                 name: token_info.name,
                 description: token_info.description,
-                thumbnail: token_info.thumbnail,
+                thumbnail,
                 frontend: token_info.frontend,
                 principal_id: token_info.principal_id,
                 submitter: token.unwrap().submitter,
                 last_updated_by: *caller,
                 last_updated_at: ic::time(),
-                details: token_info.details.clone(),
+                details,
+                ledger,
+                verification,
+                relationships,
             };
 
-            self.0.insert(token_info.principal_id, updated_token);
+            if let Some(token) = self.0.insert(token_info.principal_id, updated_token) {
+                self.1.remove(&token);
+                self.2.remove(&token);
+            }
         }
         // Its a new entry
         else {
             let new_token = Token {
                 name: token_info.name,
                 description: token_info.description,
-                thumbnail: token_info.thumbnail,
+                thumbnail,
                 frontend: token_info.frontend,
                 principal_id: token_info.principal_id,
                 submitter: *caller,
                 last_updated_by: *caller,
                 last_updated_at: ic::time(),
-                details: token_info.details.clone(),
+                details,
+                ledger,
+                verification,
+                relationships,
             };
 
             self.0.insert(token_info.principal_id, new_token);
         }
 
+        self.1.insert(&self.0[&token_info.principal_id]);
+        self.2.insert(&self.0[&token_info.principal_id]);
+
         Ok(())
     }
 
@@ -89,11 +165,26 @@ impl TokenRegistry {
             return Err(OperationError::NotAuthorized);
         }
 
+        self.1.remove(token);
+        self.2.remove(token);
         self.0.remove(principal_id);
 
         return Ok(());
     }
 
+    pub fn set_thumbnail(&mut self, principal_id: &Principal, thumbnail: String) {
+        if let Some(token) = self.0.get_mut(principal_id) {
+            token.thumbnail = thumbnail;
+        }
+    }
+
+    pub fn set_verification(&mut self, principal_id: &Principal, record: VerificationRecord) {
+        if let Some(token) = self.0.get_mut(principal_id) {
+            set_verified(&mut token.details, record.verified);
+            token.verification = Some(record);
+        }
+    }
+
     pub fn get_info(&self, principal_id: &Principal) -> Option<&Token> {
         self.0.get(principal_id)
     }
@@ -101,11 +192,39 @@ impl TokenRegistry {
     pub fn get_all(&self) -> Vec<&Token> {
         self.0.values().collect()
     }
+
+    // The tokens that the token points at and the ones that point at it
+    pub fn get_related(&self, principal_id: &Principal) -> Vec<&Token> {
+        let mut related: BTreeSet<Principal> = self.2.get(principal_id).copied().collect();
+        if let Some(token) = self.0.get(principal_id) {
+            for relationship in relationships(token) {
+                related.extend(relationship.principals());
+            }
+        }
+
+        related
+            .iter()
+            .filter_map(|principal_id| self.0.get(principal_id))
+            .collect()
+    }
+
+    // The verified tokens come first
+    pub fn get_by_symbol(&self, symbol: &str) -> Vec<&Token> {
+        let mut tokens: Vec<&Token> = self
+            .1
+            .get(symbol)
+            .filter_map(|principal_id| self.0.get(principal_id))
+            .collect();
+        tokens.sort_by_key(|token| !is_verified(&token.details));
+        tokens
+    }
 }
 
 #[init]
-pub fn init() {
+pub fn init(config: Option<Config>) {
     ic::store(Admins(vec![ic::caller()]));
+    configure(config);
+    start_token_list();
 }
 
 #[query]
@@ -113,10 +232,10 @@ pub fn name() -> String {
     String::from("Token Registry Canister")
 }
 
-#[update]
-pub async fn add(
+pub async fn add_with<M: MetadataSource>(
+    source: &M,
     trusted_source: Option<Principal>,
-    token: AddTokenInput,
+    mut token: AddTokenInput,
 ) -> Result<(), OperationError> {
     // Check authorization
     let caller = ic::caller();
@@ -132,46 +251,47 @@ pub async fn add(
     }
 
     // Check Character Limits
-    let name = token.name.clone();
-    if name.len() > 120 && token.description.len() > 1200 {
+    let config = ic::get::<Config>();
+    if token.name.len() > config.name_limit || token.description.len() > config.description_limit {
         return Err(OperationError::BadParameters);
     }
 
-    // Check details
-    if token.details.len() != 6
-        || token.details[0].0 != String::from("symbol")
-        || token.details[1].0 != String::from("standard")
-        || token.details[2].0 != String::from("total_supply")
-        || token.details[3].0 != String::from("verified")
-        || (token.details[3].1 != DetailValue::True && token.details[3].1 != DetailValue::False)
-        || token.details[4].0 != String::from("decimals")
-        || token.details[5].0 != String::from("fee")
-    {
-        return Err(OperationError::BadParameters);
+    // Check details, they are stored in their typed order
+    let (token_details, details) = validate_details(&token.details, &config.allowed_detail_keys)?;
+    token.details = details;
+    if let Some(relationships) = &token.relationships {
+        validate_relationships(&token.principal_id, relationships)?;
     }
 
-    // Add the collection to the canister registry
-    let mut call_arg = token.clone();
-    call_arg.details = vec![(
-        "category".to_string(),
-        DetailValue::Text("Token".to_string()),
-    )];
+    ic::get::<TokenRegistry>().check_symbol(&token)?;
 
-    let _registry_add_response: RegistryResponse = match ic::call(
-        Principal::from_str(CANISTER_REGISTRY_ID).unwrap(),
-        "add",
-        (trusted_source.unwrap_or(ic::id()), call_arg),
-    )
-    .await
-    {
-        Ok((x,)) => x,
-        Err((_code, msg)) => {
-            return Err(OperationError::Unknown(msg));
-        }
-    };
+    // The details have to match the ones the token canister reports
+    let ledger = check_metadata(source, token.principal_id, &token_details).await?;
 
+    // The symbol can have been taken by a verified token during the call
     let db = ic::get_mut::<TokenRegistry>();
-    return db.add(&trusted_source.unwrap_or(caller), token);
+    db.check_symbol(&token)?;
+    let is_update = db.get_info(&token.principal_id).is_some();
+    let principal_id = token.principal_id;
+    db.add(&trusted_source.unwrap_or(caller), token.clone(), ledger)?;
+    schedule(&token, trusted_source);
+
+    match is_update {
+        true => token_edited(),
+        false => token_added(),
+    }
+
+    // List the token in the canister registry, a failed call stays pending until it is retried
+    sync(principal_id).await.ok();
+    Ok(())
+}
+
+#[update]
+pub async fn add(
+    trusted_source: Option<Principal>,
+    token: AddTokenInput,
+) -> Result<(), OperationError> {
+    add_with(&TokenCanister, trusted_source, token).await
 }
 
 #[update]
@@ -186,7 +306,11 @@ pub fn remove(
     }
 
     let db = ic::get_mut::<TokenRegistry>();
-    return db.remove(&trusted_source.unwrap_or(caller), &principal_id);
+    db.remove(&trusted_source.unwrap_or(caller), &principal_id)?;
+    ic::get_mut::<Logos>().remove(&principal_id);
+    ic::get_mut::<PendingOperations>().remove(&principal_id);
+    token_removed();
+    Ok(())
 }
 
 #[query]
@@ -195,6 +319,27 @@ pub fn get(principal_id: Principal) -> Option<&'static Token> {
     db.get_info(&principal_id)
 }
 
+#[query]
+pub fn get_token_details(principal_id: Principal) -> Option<TokenDetails> {
+    let db = ic::get_mut::<TokenRegistry>();
+    db.get_info(&principal_id)
+        .and_then(|token| TokenDetails::try_from(token.details.as_slice()).ok())
+}
+
+// Resolves a symbol to its tokens, ignoring case
+#[query]
+pub fn get_by_symbol(symbol: String) -> Vec<&'static Token> {
+    let db = ic::get_mut::<TokenRegistry>();
+    db.get_by_symbol(&symbol)
+}
+
+// The wrapped, bridged and pooled tokens that are related to the token
+#[query]
+pub fn get_related(principal_id: Principal) -> Vec<&'static Token> {
+    let db = ic::get_mut::<TokenRegistry>();
+    db.get_related(&principal_id)
+}
+
 #[query]
 pub fn get_all() -> Vec<&'static Token> {
     let db = ic::get_mut::<TokenRegistry>();
@@ -1,6 +1,11 @@
 use crate::common_types::*;
+use crate::logos::Logos;
 use crate::management::Admins;
+use crate::reconciliation::PendingOperations;
+use crate::symbols::is_verified;
+use crate::tokenlist::start_token_list;
 use crate::tokens::TokenRegistry;
+use crate::verification::Verifications;
 use ic_kit::candid::{CandidType, Deserialize, Principal};
 use ic_kit::ic::*;
 use ic_kit::macros::*;
@@ -10,14 +15,32 @@ use ic_kit::*;
 pub struct StableStorage {
     db: Vec<(Principal, Token)>,
     admins: Vec<Principal>,
+    config: Option<Config>,
+    verifications: Option<Vec<(Principal, Vec<VerificationRecord>)>>,
+    logos: Option<Vec<(Principal, Logo)>>,
+    token_list_version: Option<TokenListVersion>,
+    pending_operations: Option<Vec<PendingOperation>>,
 }
 
 #[pre_upgrade]
 pub fn pre_upgrade() {
     let db = ic::get_mut::<TokenRegistry>().archive();
     let admins = ic::get_mut::<Admins>().0.clone();
+    let config = Some(ic::get::<Config>().clone());
+    let verifications = Some(ic::get_mut::<Verifications>().archive());
+    let logos = Some(ic::get_mut::<Logos>().archive());
+    let token_list_version = Some(ic::get::<TokenListVersion>().clone());
+    let pending_operations = Some(ic::get_mut::<PendingOperations>().archive());
 
-    let stable = StableStorage { db, admins };
+    let stable = StableStorage {
+        db,
+        admins,
+        config,
+        verifications,
+        logos,
+        token_list_version,
+        pending_operations,
+    };
 
     match ic::stable_store((stable,)) {
         Ok(_) => (),
@@ -36,5 +59,42 @@ pub fn post_upgrade() {
         ic::get_mut::<TokenRegistry>().load(stable.db);
         //ic::store(Admins(stable.controllers));
         ic::store(Admins(stable.admins));
+        ic::store(stable.config.unwrap_or_default());
+
+        match stable.verifications {
+            Some(verifications) => ic::get_mut::<Verifications>().load(verifications),
+            None => migrate_verified_tokens(),
+        }
+        ic::get_mut::<Logos>().load(stable.logos.unwrap_or_default());
+        ic::get_mut::<PendingOperations>().load(stable.pending_operations.unwrap_or_default());
+
+        match stable.token_list_version {
+            Some(version) => ic::store(version),
+            None => start_token_list(),
+        }
+    }
+}
+
+// The tokens that were verified through their details before the verification process keep their
+// status, with a record made by the registry itself
+fn migrate_verified_tokens() {
+    let db = ic::get_mut::<TokenRegistry>();
+    let verified: Vec<Principal> = db
+        .get_all()
+        .into_iter()
+        .filter(|token| is_verified(&token.details))
+        .map(|token| token.principal_id)
+        .collect();
+
+    for principal_id in verified {
+        let record = VerificationRecord {
+            verified: true,
+            reason: String::from("Verified before the verification process"),
+            changed_by: ic::id(),
+            changed_at: ic::time(),
+        };
+
+        ic::get_mut::<Verifications>().record(principal_id, record.clone());
+        db.set_verification(&principal_id, record);
     }
 }
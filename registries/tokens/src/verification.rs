@@ -0,0 +1,89 @@
+use ic_kit::candid::Principal;
+use ic_kit::macros::*;
+use ic_kit::*;
+use std::collections::HashMap;
+
+use crate::common_types::*;
+use crate::management::is_admin;
+use crate::tokenlist::token_edited;
+use crate::tokens::TokenRegistry;
+
+// The changes of the verified status of each token, oldest first. The history is kept when a token is
+// removed, so that it is still verified if it is listed again.
+#[derive(Default)]
+pub struct Verifications(HashMap<Principal, Vec<VerificationRecord>>);
+
+impl Verifications {
+    pub fn archive(&mut self) -> Vec<(Principal, Vec<VerificationRecord>)> {
+        let map = std::mem::take(&mut self.0);
+        map.into_iter().collect()
+    }
+
+    pub fn load(&mut self, archive: Vec<(Principal, Vec<VerificationRecord>)>) {
+        self.0 = archive.into_iter().collect();
+    }
+
+    pub fn latest(&self, principal_id: &Principal) -> Option<&VerificationRecord> {
+        self.0.get(principal_id).and_then(|history| history.last())
+    }
+
+    pub fn is_verified(&self, principal_id: &Principal) -> bool {
+        matches!(self.latest(principal_id), Some(record) if record.verified)
+    }
+
+    pub fn history(&self, principal_id: &Principal) -> Vec<&VerificationRecord> {
+        self.0.get(principal_id).into_iter().flatten().collect()
+    }
+
+    pub fn record(&mut self, principal_id: Principal, record: VerificationRecord) {
+        self.0.entry(principal_id).or_default().push(record);
+    }
+}
+
+fn set_verified(
+    principal_id: Principal,
+    verified: bool,
+    reason: String,
+) -> Result<(), OperationError> {
+    let caller = ic::caller();
+    if !is_admin(&caller) {
+        return Err(OperationError::NotAuthorized);
+    }
+
+    let db = ic::get_mut::<TokenRegistry>();
+    if db.get_info(&principal_id).is_none() {
+        return Err(OperationError::NonExistentItem);
+    }
+
+    let verifications = ic::get_mut::<Verifications>();
+    if reason.trim().is_empty() || verifications.is_verified(&principal_id) == verified {
+        return Err(OperationError::BadParameters);
+    }
+
+    let record = VerificationRecord {
+        verified,
+        reason,
+        changed_by: caller,
+        changed_at: ic::time(),
+    };
+
+    verifications.record(principal_id, record.clone());
+    db.set_verification(&principal_id, record);
+    token_edited();
+    Ok(())
+}
+
+#[update]
+pub fn verify_token(principal_id: Principal, reason: String) -> Result<(), OperationError> {
+    set_verified(principal_id, true, reason)
+}
+
+#[update]
+pub fn unverify_token(principal_id: Principal, reason: String) -> Result<(), OperationError> {
+    set_verified(principal_id, false, reason)
+}
+
+#[query]
+pub fn get_verification_history(principal_id: Principal) -> Vec<&'static VerificationRecord> {
+    ic::get::<Verifications>().history(&principal_id)
+}